@@ -1,5 +1,9 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use jsonwebtoken_rustcrypto::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken_rustcrypto::crypto::{sign, verify};
+use jsonwebtoken_rustcrypto::{
+    decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use rsa::pkcs1::DecodeRsaPrivateKey;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -28,5 +32,60 @@ fn bench_decode(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, bench_encode, bench_decode);
+const HMAC_ALGORITHMS: &[Algorithm] = &[Algorithm::HS256, Algorithm::HS384, Algorithm::HS512];
+const RSA_ALGORITHMS: &[Algorithm] = &[
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+];
+
+fn rsa_keys() -> (EncodingKey, DecodingKey) {
+    let privkey =
+        rsa::RsaPrivateKey::from_pkcs1_pem(include_str!("../tests/rsa/private_rsa_key_pkcs1.pem"))
+            .unwrap();
+    let pubkey = privkey.to_public_key();
+    (EncodingKey::from_rsa(privkey).unwrap(), DecodingKey::from_rsa(pubkey).unwrap())
+}
+
+fn bench_sign(c: &mut Criterion) {
+    let message = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.eyJzdWIiOiIxMjM0NTY3ODkwIiwibmFtZSI6IkpvaG4gRG9lIiwiYWRtaW4iOnRydWV9";
+    let hmac_key = EncodingKey::from_hmac_secret("secret".as_ref());
+    let (rsa_key, _) = rsa_keys();
+
+    for &alg in HMAC_ALGORITHMS {
+        c.bench_function(&format!("sign_{:?}", alg), |b| {
+            b.iter(|| sign(black_box(message), black_box(&hmac_key), alg))
+        });
+    }
+    for &alg in RSA_ALGORITHMS {
+        c.bench_function(&format!("sign_{:?}", alg), |b| {
+            b.iter(|| sign(black_box(message), black_box(&rsa_key), alg))
+        });
+    }
+}
+
+fn bench_verify(c: &mut Criterion) {
+    let message = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.eyJzdWIiOiIxMjM0NTY3ODkwIiwibmFtZSI6IkpvaG4gRG9lIiwiYWRtaW4iOnRydWV9";
+    let (rsa_encoding_key, rsa_key) = rsa_keys();
+    let hmac_encoding_key = EncodingKey::from_hmac_secret("secret".as_ref());
+    let hmac_key = DecodingKey::from_hmac_secret("secret".as_ref());
+
+    for &alg in HMAC_ALGORITHMS {
+        let signature = sign(message, &hmac_encoding_key, alg).unwrap();
+        c.bench_function(&format!("verify_{:?}", alg), |b| {
+            b.iter(|| verify(black_box(&signature), black_box(message), black_box(&hmac_key), alg))
+        });
+    }
+    for &alg in RSA_ALGORITHMS {
+        let signature = sign(message, &rsa_encoding_key, alg).unwrap();
+        c.bench_function(&format!("verify_{:?}", alg), |b| {
+            b.iter(|| verify(black_box(&signature), black_box(message), black_box(&rsa_key), alg))
+        });
+    }
+}
+
+criterion_group!(benches, bench_encode, bench_decode, bench_sign, bench_verify);
 criterion_main!(benches);
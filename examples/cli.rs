@@ -0,0 +1,89 @@
+//! A tiny command line tool to create and inspect tokens.
+//!
+//! ```sh
+//! # HMAC, the key file contains the raw secret
+//! cargo run --example cli -- encode secret.txt '{"sub":"b@b.com","exp":10000000000}' HS256
+//! cargo run --example cli -- decode secret.txt <token>
+//!
+//! # RSA, the key files are PEM encoded (PKCS#1 or PKCS#8)
+//! cargo run --example cli -- encode tests/rsa/private_rsa_key_pkcs8.pem '{"sub":"b@b.com"}' RS256
+//! cargo run --example cli -- decode tests/rsa/public_rsa_key_pkcs8.pem <token>
+//! ```
+use std::str::FromStr;
+use std::{env, fs, process};
+
+use jsonwebtoken_rustcrypto::{
+    decode, decode_header, encode, Algorithm, AlgorithmFamily, DecodingKey, EncodingKey, Header,
+    Validation,
+};
+use rsa::pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey};
+use rsa::pkcs8::{DecodePrivateKey, DecodePublicKey};
+
+type CliResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+fn encoding_key(path: &str, alg: Algorithm) -> CliResult<EncodingKey> {
+    if alg.family() == AlgorithmFamily::Hmac {
+        return Ok(EncodingKey::from_hmac_secret(&fs::read(path)?));
+    }
+    let pem = fs::read_to_string(path)?;
+    let key = rsa::RsaPrivateKey::from_pkcs8_pem(&pem)
+        .or_else(|_| rsa::RsaPrivateKey::from_pkcs1_pem(&pem))?;
+    Ok(EncodingKey::from_rsa(key)?)
+}
+
+fn decoding_key(path: &str, alg: Algorithm) -> CliResult<DecodingKey> {
+    if alg.family() == AlgorithmFamily::Hmac {
+        return Ok(DecodingKey::from_hmac_secret(&fs::read(path)?));
+    }
+    let pem = fs::read_to_string(path)?;
+    let key = rsa::RsaPublicKey::from_public_key_pem(&pem)
+        .or_else(|_| rsa::RsaPublicKey::from_pkcs1_pem(&pem))?;
+    Ok(DecodingKey::from_rsa(key)?)
+}
+
+fn run(args: &[String]) -> CliResult<()> {
+    match args {
+        [command, key_path, claims] if command == "encode" => run_encode(key_path, claims, "HS256"),
+        [command, key_path, claims, alg] if command == "encode" => {
+            run_encode(key_path, claims, alg)
+        }
+        [command, key_path, token] if command == "decode" => run_decode(key_path, token),
+        _ => {
+            eprintln!("usage: cli encode <key file> <claims json> [algorithm]");
+            eprintln!("       cli decode <key file> <token>");
+            process::exit(2);
+        }
+    }
+}
+
+fn run_encode(key_path: &str, claims: &str, alg: &str) -> CliResult<()> {
+    let alg = Algorithm::from_str(alg)?;
+    let claims: serde_json::Value = serde_json::from_str(claims)?;
+    let token = encode(&Header::new(alg), &claims, &encoding_key(key_path, alg)?)?;
+    println!("{}", token);
+    Ok(())
+}
+
+fn run_decode(key_path: &str, token: &str) -> CliResult<()> {
+    // The header tells us which kind of key to load, it is checked again by `decode`
+    let alg = decode_header(token)?.alg;
+    // Only the signature is checked, tokens without an `exp` are common when testing
+    let validation = Validation {
+        validate_exp: false,
+        required_spec_claims: Default::default(),
+        ..Validation::new(alg)
+    };
+    let token_data =
+        decode::<serde_json::Value>(token, &decoding_key(key_path, alg)?, &validation)?;
+    println!("{}", serde_json::to_string_pretty(&token_data.header)?);
+    println!("{}", serde_json::to_string_pretty(&token_data.claims)?);
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(err) = run(&args) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
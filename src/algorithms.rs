@@ -1,16 +1,21 @@
 use crate::errors::{Error, ErrorKind, Result};
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
 use std::str::FromStr;
 
+/// The kind of key an `Algorithm` works with
 #[derive(Debug, Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
-pub(crate) enum AlgorithmFamily {
+pub enum AlgorithmFamily {
+    /// HS* algorithms, using a shared secret
     Hmac,
+    /// RS* and PS* algorithms
     Rsa,
+    /// ES* algorithms
     Ec,
 }
 
 /// The algorithms supported for signing/verifying JWTs
-#[derive(Debug, PartialEq, Hash, Copy, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Default)]
 pub enum Algorithm {
     /// HMAC using SHA-256
     #[default]
@@ -19,6 +24,15 @@ pub enum Algorithm {
     HS384,
     /// HMAC using SHA-512
     HS512,
+    /// HMAC using SHA3-256. Not a registered JWA algorithm, its `alg` is `HS3-256`.
+    #[cfg(feature = "sha3")]
+    HS3_256,
+    /// HMAC using SHA3-384. Not a registered JWA algorithm, its `alg` is `HS3-384`.
+    #[cfg(feature = "sha3")]
+    HS3_384,
+    /// HMAC using SHA3-512. Not a registered JWA algorithm, its `alg` is `HS3-512`.
+    #[cfg(feature = "sha3")]
+    HS3_512,
 
     /// ECDSA using SHA-256
     ES256,
@@ -39,23 +53,201 @@ pub enum Algorithm {
     PS512,
 }
 
+/// Every `Algorithm` variant, in declaration order
+const ALGORITHMS: &[Algorithm] = &[
+    Algorithm::HS256,
+    Algorithm::HS384,
+    Algorithm::HS512,
+    #[cfg(feature = "sha3")]
+    Algorithm::HS3_256,
+    #[cfg(feature = "sha3")]
+    Algorithm::HS3_384,
+    #[cfg(feature = "sha3")]
+    Algorithm::HS3_512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+];
+
+impl Algorithm {
+    /// All the HS* algorithms, for example to accept any of them with
+    /// `Validation::set_algorithms(Algorithm::hmac_all())`
+    pub fn hmac_all() -> &'static [Algorithm] {
+        &[
+            Algorithm::HS256,
+            Algorithm::HS384,
+            Algorithm::HS512,
+            #[cfg(feature = "sha3")]
+            Algorithm::HS3_256,
+            #[cfg(feature = "sha3")]
+            Algorithm::HS3_384,
+            #[cfg(feature = "sha3")]
+            Algorithm::HS3_512,
+        ]
+    }
+
+    /// All the RS* and PS* algorithms
+    pub fn rsa_all() -> &'static [Algorithm] {
+        &[
+            Algorithm::RS256,
+            Algorithm::RS384,
+            Algorithm::RS512,
+            Algorithm::PS256,
+            Algorithm::PS384,
+            Algorithm::PS512,
+        ]
+    }
+
+    /// All the ES* algorithms. They aren't supported for signing or verifying yet.
+    pub fn ec_all() -> &'static [Algorithm] {
+        &[Algorithm::ES256, Algorithm::ES384]
+    }
+
+    /// The name of the algorithm as registered in [RFC7518](https://tools.ietf.org/html/rfc7518#section-3.1),
+    /// used for the `alg` header. `Display`, `FromStr` and serde all go through it.
+    pub fn jwa_name(&self) -> &'static str {
+        match self {
+            Algorithm::HS256 => "HS256",
+            Algorithm::HS384 => "HS384",
+            Algorithm::HS512 => "HS512",
+            #[cfg(feature = "sha3")]
+            Algorithm::HS3_256 => "HS3-256",
+            #[cfg(feature = "sha3")]
+            Algorithm::HS3_384 => "HS3-384",
+            #[cfg(feature = "sha3")]
+            Algorithm::HS3_512 => "HS3-512",
+            Algorithm::ES256 => "ES256",
+            Algorithm::ES384 => "ES384",
+            Algorithm::RS256 => "RS256",
+            Algorithm::RS384 => "RS384",
+            Algorithm::RS512 => "RS512",
+            Algorithm::PS256 => "PS256",
+            Algorithm::PS384 => "PS384",
+            Algorithm::PS512 => "PS512",
+        }
+    }
+
+    /// The kind of key the algorithm works with
+    pub fn family(&self) -> AlgorithmFamily {
+        match self {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => AlgorithmFamily::Hmac,
+            #[cfg(feature = "sha3")]
+            Algorithm::HS3_256 | Algorithm::HS3_384 | Algorithm::HS3_512 => AlgorithmFamily::Hmac,
+            Algorithm::ES256 | Algorithm::ES384 => AlgorithmFamily::Ec,
+            Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512 => AlgorithmFamily::Rsa,
+        }
+    }
+
+    /// An estimate of the security level of the algorithm in bits, to enforce minimum strength
+    /// policies such as `alg.security_bits() >= 128`.
+    ///
+    /// RSA depends on the key size rather than the algorithm, so all RSA algorithms are rated for
+    /// the 2048 bits keys commonly used with them (112 bits, per NIST SP 800-57).
+    pub fn security_bits(&self) -> u32 {
+        match self {
+            Algorithm::HS256 | Algorithm::ES256 => 128,
+            Algorithm::HS384 | Algorithm::ES384 => 192,
+            Algorithm::HS512 => 256,
+            #[cfg(feature = "sha3")]
+            Algorithm::HS3_256 => 128,
+            #[cfg(feature = "sha3")]
+            Algorithm::HS3_384 => 192,
+            #[cfg(feature = "sha3")]
+            Algorithm::HS3_512 => 256,
+            Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512 => 112,
+        }
+    }
+
+    /// The output length in bytes of the hash function used by the algorithm
+    pub fn hash_len(&self) -> usize {
+        match self {
+            Algorithm::HS256 | Algorithm::ES256 | Algorithm::RS256 | Algorithm::PS256 => 32,
+            Algorithm::HS384 | Algorithm::ES384 | Algorithm::RS384 | Algorithm::PS384 => 48,
+            Algorithm::HS512 | Algorithm::RS512 | Algorithm::PS512 => 64,
+            #[cfg(feature = "sha3")]
+            Algorithm::HS3_256 => 32,
+            #[cfg(feature = "sha3")]
+            Algorithm::HS3_384 => 48,
+            #[cfg(feature = "sha3")]
+            Algorithm::HS3_512 => 64,
+        }
+    }
+
+    /// The length in bytes of the signatures produced by the algorithm, or `None` if it
+    /// depends on the key, as it does for RSA
+    pub fn signature_len(&self) -> Option<usize> {
+        match self {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => Some(self.hash_len()),
+            #[cfg(feature = "sha3")]
+            Algorithm::HS3_256 | Algorithm::HS3_384 | Algorithm::HS3_512 => Some(self.hash_len()),
+            Algorithm::ES256 => Some(64),
+            Algorithm::ES384 => Some(96),
+            Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512 => None,
+        }
+    }
+}
+
 impl FromStr for Algorithm {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self> {
-        match s {
-            "HS256" => Ok(Algorithm::HS256),
-            "HS384" => Ok(Algorithm::HS384),
-            "HS512" => Ok(Algorithm::HS512),
-            "ES256" => Ok(Algorithm::ES256),
-            "ES384" => Ok(Algorithm::ES384),
-            "RS256" => Ok(Algorithm::RS256),
-            "RS384" => Ok(Algorithm::RS384),
-            "PS256" => Ok(Algorithm::PS256),
-            "PS384" => Ok(Algorithm::PS384),
-            "PS512" => Ok(Algorithm::PS512),
-            "RS512" => Ok(Algorithm::RS512),
-            _ => Err(ErrorKind::InvalidAlgorithmName.into()),
+        ALGORITHMS
+            .iter()
+            .copied()
+            .find(|alg| alg.jwa_name() == s)
+            .ok_or_else(|| ErrorKind::InvalidAlgorithmName.into())
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.jwa_name())
+    }
+}
+
+impl Serialize for Algorithm {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.jwa_name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Algorithm {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct AlgorithmVisitor;
+
+        impl<'de> de::Visitor<'de> for AlgorithmVisitor {
+            type Value = Algorithm;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a JWA algorithm name")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> std::result::Result<Algorithm, E> {
+                Algorithm::from_str(value)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+            }
         }
+
+        deserializer.deserialize_str(AlgorithmVisitor)
     }
 }
 
@@ -63,6 +255,20 @@ impl FromStr for Algorithm {
 mod tests {
     use super::*;
 
+    #[test]
+    fn family_groups() {
+        let groups = [
+            (Algorithm::hmac_all(), AlgorithmFamily::Hmac),
+            (Algorithm::rsa_all(), AlgorithmFamily::Rsa),
+            (Algorithm::ec_all(), AlgorithmFamily::Ec),
+        ];
+        for (algorithms, family) in groups {
+            assert!(algorithms.iter().all(|alg| alg.family() == family));
+        }
+        let grouped: usize = groups.iter().map(|(algorithms, _)| algorithms.len()).sum();
+        assert_eq!(grouped, ALGORITHMS.len());
+    }
+
     #[test]
     fn generate_algorithm_enum_from_str() {
         assert!(Algorithm::from_str("HS256").is_ok());
@@ -76,4 +282,46 @@ mod tests {
         assert!(Algorithm::from_str("PS512").is_ok());
         assert!(Algorithm::from_str("").is_err());
     }
+
+    #[test]
+    fn jwa_name_matches_serde_and_display() {
+        for &alg in ALGORITHMS {
+            assert_eq!(serde_json::to_string(&alg).unwrap(), format!("\"{}\"", alg.jwa_name()));
+            assert_eq!(alg.to_string(), alg.jwa_name());
+            assert_eq!(Algorithm::from_str(alg.jwa_name()).unwrap(), alg);
+            let from_json: Algorithm =
+                serde_json::from_str(&format!("\"{}\"", alg.jwa_name())).unwrap();
+            assert_eq!(from_json, alg);
+        }
+        assert!(serde_json::from_str::<Algorithm>("\"none\"").is_err());
+    }
+
+    #[test]
+    fn hash_and_signature_lengths() {
+        assert_eq!(Algorithm::HS256.hash_len(), 32);
+        assert_eq!(Algorithm::HS256.signature_len(), Some(32));
+        assert_eq!(Algorithm::HS512.signature_len(), Some(64));
+        assert_eq!(Algorithm::ES256.signature_len(), Some(64));
+        assert_eq!(Algorithm::ES384.hash_len(), 48);
+        assert_eq!(Algorithm::ES384.signature_len(), Some(96));
+        assert_eq!(Algorithm::RS384.hash_len(), 48);
+        assert_eq!(Algorithm::PS512.signature_len(), None);
+    }
+
+    #[test]
+    fn algorithm_family() {
+        assert_eq!(Algorithm::HS384.family(), AlgorithmFamily::Hmac);
+        assert_eq!(Algorithm::PS256.family(), AlgorithmFamily::Rsa);
+        assert_eq!(Algorithm::ES256.family(), AlgorithmFamily::Ec);
+    }
+
+    #[test]
+    fn minimum_security_policy() {
+        let offered = [Algorithm::RS256, Algorithm::HS256, Algorithm::ES384, Algorithm::PS512];
+        let accepted: Vec<_> =
+            offered.iter().copied().filter(|alg| alg.security_bits() >= 128).collect();
+        assert_eq!(accepted, vec![Algorithm::HS256, Algorithm::ES384]);
+        assert!(Algorithm::HS512.security_bits() > Algorithm::HS384.security_bits());
+        assert!(Algorithm::ES256.security_bits() > Algorithm::RS512.security_bits());
+    }
 }
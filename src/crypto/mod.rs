@@ -2,42 +2,122 @@ use hmac::{Hmac, Mac};
 
 use crate::algorithms::Algorithm;
 use crate::decoding::DecodingKey;
-use crate::encoding::EncodingKey;
+use crate::encoding::{encode_raw, EncodingKey};
 use crate::errors::{new_error, ErrorKind, Result};
+use crate::header::Header;
 use crate::serialization::{b64_decode, b64_encode};
 
 use sha2::{Sha256, Sha384, Sha512};
 // pub(crate) mod ecdsa;
+mod remote;
 pub(crate) mod rsa;
+mod streaming;
+
+pub use self::rsa::Mgf1Hash;
+pub use remote::{RemoteSigner, RemoteSigningKey, RemoteVerifier, RemoteVerifyingKey};
+pub use streaming::{Signer, Verifier};
 
 type HmacSha256 = Hmac<Sha256>;
 type HmacSha384 = Hmac<Sha384>;
 type HmacSha512 = Hmac<Sha512>;
-/// The actual HS signing + encoding
+#[cfg(feature = "sha3")]
+type HmacSha3_256 = Hmac<sha3::Sha3_256>;
+#[cfg(feature = "sha3")]
+type HmacSha3_384 = Hmac<sha3::Sha3_384>;
+#[cfg(feature = "sha3")]
+type HmacSha3_512 = Hmac<sha3::Sha3_512>;
+/// The actual HS signing
 /// Could be in its own file to match RSA/EC but it's 2 lines...
-pub(crate) fn sign_hmac(alg: Algorithm, key: &[u8], message: &str) -> Result<String> {
+pub(crate) fn sign_hmac(alg: Algorithm, key: &[u8], message: &[u8]) -> Result<Vec<u8>> {
     // println!("alg: {:?}\nkey: {:?}\nmessage: {:?}");
 
     // let digest = hmac::sign(&hmac::Key::new(alg, key), message.as_bytes());
     let digest = match alg {
+        Algorithm::HS256 => {
+            let mut mac = HmacSha256::new_from_slice(key).unwrap();
+            mac.update(message);
+            mac.finalize().into_bytes().to_vec()
+        }
+        Algorithm::HS384 => {
+            let mut mac = HmacSha384::new_from_slice(key).unwrap();
+            mac.update(message);
+            mac.finalize().into_bytes().to_vec()
+        }
+        Algorithm::HS512 => {
+            let mut mac = HmacSha512::new_from_slice(key).unwrap();
+            mac.update(message);
+            mac.finalize().into_bytes().to_vec()
+        }
+        #[cfg(feature = "sha3")]
+        Algorithm::HS3_256 => {
+            let mut mac = HmacSha3_256::new_from_slice(key).unwrap();
+            mac.update(message);
+            mac.finalize().into_bytes().to_vec()
+        }
+        #[cfg(feature = "sha3")]
+        Algorithm::HS3_384 => {
+            let mut mac = HmacSha3_384::new_from_slice(key).unwrap();
+            mac.update(message);
+            mac.finalize().into_bytes().to_vec()
+        }
+        #[cfg(feature = "sha3")]
+        Algorithm::HS3_512 => {
+            let mut mac = HmacSha3_512::new_from_slice(key).unwrap();
+            mac.update(message);
+            mac.finalize().into_bytes().to_vec()
+        }
+        _ => unreachable!(),
+    };
+    Ok(digest)
+}
+
+/// The actual HS verification.
+/// `verify_slice` compares in constant time and requires the signature to be the full MAC
+/// length, so truncated signatures are rejected.
+pub(crate) fn verify_hmac(
+    alg: Algorithm,
+    key: &[u8],
+    message: &str,
+    signature: &str,
+) -> Result<bool> {
+    let signature = b64_decode(signature).map_err(|_e| new_error(ErrorKind::InvalidSignature))?;
+    let valid = match alg {
         Algorithm::HS256 => {
             let mut mac = HmacSha256::new_from_slice(key).unwrap();
             mac.update(message.as_bytes());
-            b64_encode(mac.finalize().into_bytes().as_slice())
+            mac.verify_slice(&signature).is_ok()
         }
         Algorithm::HS384 => {
             let mut mac = HmacSha384::new_from_slice(key).unwrap();
             mac.update(message.as_bytes());
-            b64_encode(mac.finalize().into_bytes().as_slice())
+            mac.verify_slice(&signature).is_ok()
         }
         Algorithm::HS512 => {
             let mut mac = HmacSha512::new_from_slice(key).unwrap();
             mac.update(message.as_bytes());
-            b64_encode(mac.finalize().into_bytes().as_slice())
+            mac.verify_slice(&signature).is_ok()
+        }
+        #[cfg(feature = "sha3")]
+        Algorithm::HS3_256 => {
+            let mut mac = HmacSha3_256::new_from_slice(key).unwrap();
+            mac.update(message.as_bytes());
+            mac.verify_slice(&signature).is_ok()
+        }
+        #[cfg(feature = "sha3")]
+        Algorithm::HS3_384 => {
+            let mut mac = HmacSha3_384::new_from_slice(key).unwrap();
+            mac.update(message.as_bytes());
+            mac.verify_slice(&signature).is_ok()
+        }
+        #[cfg(feature = "sha3")]
+        Algorithm::HS3_512 => {
+            let mut mac = HmacSha3_512::new_from_slice(key).unwrap();
+            mac.update(message.as_bytes());
+            mac.verify_slice(&signature).is_ok()
         }
         _ => unreachable!(),
     };
-    Ok(digest)
+    Ok(valid)
 }
 
 /// Validates that the key can be used with the given algorithm
@@ -47,6 +127,8 @@ pub fn validate_matching_key(key: &EncodingKey, algorithm: Algorithm) -> Result<
             Algorithm::HS256 => Ok(()),
             Algorithm::HS384 => Ok(()),
             Algorithm::HS512 => Ok(()),
+            #[cfg(feature = "sha3")]
+            Algorithm::HS3_256 | Algorithm::HS3_384 | Algorithm::HS3_512 => Ok(()),
             _ => Err(ErrorKind::InvalidAlgorithm.into()),
         },
         EncodingKey::Rsa(_) => match algorithm {
@@ -58,6 +140,8 @@ pub fn validate_matching_key(key: &EncodingKey, algorithm: Algorithm) -> Result<
             | Algorithm::RS512 => Ok(()),
             _ => Err(ErrorKind::InvalidAlgorithm.into()),
         },
+        // Only the remote end knows which algorithms its key works with
+        EncodingKey::Remote(_) => Ok(()),
         // EncodingKey::EcPkcs8(_)
         //     => match algorithm {
         //         Algorithm::ES256 | Algorithm::ES384 => Ok(()),
@@ -71,11 +155,31 @@ pub fn validate_matching_key(key: &EncodingKey, algorithm: Algorithm) -> Result<
 ///
 /// If you just want to encode a JWT, use `encode` instead.
 pub fn sign(message: &str, key: &EncodingKey, algorithm: Algorithm) -> Result<String> {
+    Ok(b64_encode(&compute_signature(message.as_bytes(), key, algorithm)?))
+}
+
+/// Sign `payload` with `header.alg` and assemble the token, the same as
+/// [encode_raw](../fn.encode_raw.html) for callers that already have a `Header`.
+pub fn sign_with_header(header: &Header, payload: &[u8], key: &EncodingKey) -> Result<String> {
+    encode_raw(header, payload, key)
+}
+
+/// Like [sign](fn.sign.html), but return the raw signature or MAC bytes, for building other
+/// JWS variants.
+pub fn compute_signature(
+    message: &[u8],
+    key: &EncodingKey,
+    algorithm: Algorithm,
+) -> Result<Vec<u8>> {
     match key {
         EncodingKey::Hmac(s) => match algorithm {
             Algorithm::HS256 => sign_hmac(Algorithm::HS256, s, message),
             Algorithm::HS384 => sign_hmac(Algorithm::HS384, s, message),
             Algorithm::HS512 => sign_hmac(Algorithm::HS512, s, message),
+            #[cfg(feature = "sha3")]
+            Algorithm::HS3_256 | Algorithm::HS3_384 | Algorithm::HS3_512 => {
+                sign_hmac(algorithm, s, message)
+            }
             _ => Err(ErrorKind::InvalidAlgorithm.into()),
         },
 
@@ -88,6 +192,7 @@ pub fn sign(message: &str, key: &EncodingKey, algorithm: Algorithm) -> Result<St
             | Algorithm::PS512 => rsa::sign(algorithm, k, message),
             _ => Err(ErrorKind::InvalidAlgorithm.into()),
         },
+        EncodingKey::Remote(RemoteSigningKey(signer)) => signer.sign(message, algorithm),
         // EncodingKey::EcPkcs8(k)
         //     => match algorithm {
         //         Algorithm::ES256 | Algorithm::ES384 => {
@@ -114,26 +219,12 @@ pub fn verify(
 ) -> Result<bool> {
     match key {
         DecodingKey::Hmac(s) => match algorithm {
-            Algorithm::HS256 => {
-                let mut mac = HmacSha256::new_from_slice(s).unwrap();
-                mac.update(message.as_bytes());
-                Ok(mac.finalize().into_bytes().as_slice()
-                    == b64_decode(signature)
-                        .map_err(|_e| new_error(ErrorKind::InvalidSignature))?)
-            }
-            Algorithm::HS384 => {
-                let mut mac = HmacSha384::new_from_slice(s).unwrap();
-                mac.update(message.as_bytes());
-                Ok(mac.finalize().into_bytes().as_slice()
-                    == b64_decode(signature)
-                        .map_err(|_e| new_error(ErrorKind::InvalidSignature))?)
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+                verify_hmac(algorithm, s, message, signature)
             }
-            Algorithm::HS512 => {
-                let mut mac = HmacSha512::new_from_slice(s).unwrap();
-                mac.update(message.as_bytes());
-                Ok(mac.finalize().into_bytes().as_slice()
-                    == b64_decode(signature)
-                        .map_err(|_e| new_error(ErrorKind::InvalidSignature))?)
+            #[cfg(feature = "sha3")]
+            Algorithm::HS3_256 | Algorithm::HS3_384 | Algorithm::HS3_512 => {
+                verify_hmac(algorithm, s, message, signature)
             }
             _ => Err(ErrorKind::InvalidAlgorithm.into()),
         },
@@ -146,5 +237,140 @@ pub fn verify(
             | Algorithm::PS512 => rsa::verify(algorithm, signature, message, k),
             _ => Err(ErrorKind::InvalidAlgorithm.into()),
         },
+        DecodingKey::Remote(RemoteVerifyingKey(verifier)) => {
+            if algorithm.family() != verifier.family() {
+                return Err(ErrorKind::InvalidAlgorithm.into());
+            }
+            let signature =
+                b64_decode(signature).map_err(|_| new_error(ErrorKind::InvalidSignature))?;
+            verifier.verify(message.as_bytes(), &signature, algorithm)
+        }
+    }
+}
+
+/// Like [sign](fn.sign.html) for the PS* algorithms, but with `mgf1` as the MGF1 hash.
+///
+/// JWA uses the message digest for MGF1, which is what `sign` does. Only use this for partners
+/// that don't, regular JWT libraries won't be able to verify the signatures.
+/// Other algorithms or keys return `ErrorKind::InvalidAlgorithm`.
+pub fn sign_pss_with_mgf1(
+    message: &str,
+    key: &EncodingKey,
+    algorithm: Algorithm,
+    mgf1: Mgf1Hash,
+) -> Result<String> {
+    match (key, algorithm) {
+        (EncodingKey::Rsa(k), Algorithm::PS256 | Algorithm::PS384 | Algorithm::PS512) => {
+            Ok(b64_encode(&rsa::sign_pss_with_mgf1(algorithm, mgf1, k, message.as_bytes())?))
+        }
+        _ => Err(ErrorKind::InvalidAlgorithm.into()),
+    }
+}
+
+/// Like [verify](fn.verify.html) for the PS* algorithms, but with `mgf1` as the MGF1 hash, see
+/// [sign_pss_with_mgf1](fn.sign_pss_with_mgf1.html)
+pub fn verify_pss_with_mgf1(
+    signature: &str,
+    message: &str,
+    key: &DecodingKey,
+    algorithm: Algorithm,
+    mgf1: Mgf1Hash,
+) -> Result<bool> {
+    match (key, algorithm) {
+        (DecodingKey::Rsa(k), Algorithm::PS256 | Algorithm::PS384 | Algorithm::PS512) => {
+            rsa::verify_pss_with_mgf1(algorithm, mgf1, signature, message, k)
+        }
+        _ => Err(ErrorKind::InvalidAlgorithm.into()),
+    }
+}
+
+/// Like [verify](fn.verify.html), but return the decoded signature bytes when it is valid, for
+/// example to keep them for auditing. An invalid signature is an `ErrorKind::InvalidSignature`.
+pub fn verify_returning_sig(
+    signature: &str,
+    message: &str,
+    key: &DecodingKey,
+    algorithm: Algorithm,
+) -> Result<Vec<u8>> {
+    if !verify(signature, message, key, algorithm)? {
+        return Err(new_error(ErrorKind::InvalidSignature));
+    }
+    b64_decode(signature)
+}
+
+/// Limits on the size of the RSA keys accepted by `EncodingKey::from_rsa_with_policy` and
+/// `DecodingKey::from_rsa_with_policy`. Keys outside of them are an `ErrorKind::InvalidRsaKey`.
+///
+/// ```rust
+/// use jsonwebtoken_rustcrypto::crypto::RsaKeyPolicy;
+///
+/// // Stricter than the default
+/// let policy = RsaKeyPolicy::new().min_bits(3072);
+/// // Still accepting legacy keys
+/// let legacy = RsaKeyPolicy::new().min_bits(1024);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RsaKeyPolicy {
+    min_bits: usize,
+    max_bits: usize,
+}
+
+impl RsaKeyPolicy {
+    /// The smallest key size accepted by default, as recommended by NIST SP 800-57
+    pub const DEFAULT_MIN_BITS: usize = 2048;
+    /// The largest key size accepted by default, bigger keys only make verification slow
+    pub const DEFAULT_MAX_BITS: usize = 16384;
+
+    /// Returns the default policy, accepting keys from 2048 to 16384 bits
+    pub fn new() -> Self {
+        RsaKeyPolicy { min_bits: Self::DEFAULT_MIN_BITS, max_bits: Self::DEFAULT_MAX_BITS }
+    }
+
+    /// Set the smallest accepted key size, in bits.
+    /// The largest size is raised to `bits` if it was smaller, so the range is never empty.
+    pub fn min_bits(mut self, bits: usize) -> Self {
+        self.min_bits = bits;
+        self.max_bits = self.max_bits.max(bits);
+        self
+    }
+
+    /// Set the largest accepted key size, in bits.
+    /// The smallest size is lowered to `bits` if it was bigger, so the range is never empty.
+    pub fn max_bits(mut self, bits: usize) -> Self {
+        self.max_bits = bits;
+        self.min_bits = self.min_bits.min(bits);
+        self
+    }
+
+    /// Checks the modulus size of a key against the policy
+    pub fn check(&self, key: &impl ::rsa::traits::PublicKeyParts) -> Result<()> {
+        let bits = key.n().bits();
+        if bits < self.min_bits || bits > self.max_bits {
+            return Err(new_error(ErrorKind::InvalidRsaKey));
+        }
+        Ok(())
+    }
+}
+
+impl Default for RsaKeyPolicy {
+    fn default() -> Self {
+        RsaKeyPolicy::new()
+    }
+}
+
+/// Compares two tokens without short-circuiting on the first differing byte, so the time taken
+/// doesn't reveal how much of a secret token was guessed correctly.
+///
+/// Only the length of the tokens can be learned from timing.
+pub fn tokens_eq(a: &str, b: &str) -> bool {
+    bytes_eq(a.as_bytes(), b.as_bytes())
+}
+
+/// Constant time comparison, see [tokens_eq](fn.tokens_eq.html)
+pub(crate) fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
     }
+    let diff = a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y));
+    std::hint::black_box(diff) == 0
 }
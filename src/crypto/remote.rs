@@ -0,0 +1,61 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::algorithms::{Algorithm, AlgorithmFamily};
+use crate::errors::Result;
+
+/// Signs messages with a key that can't be loaded in the process, like one kept in a KMS or an
+/// HSM. Use it with `EncodingKey::from_remote`.
+pub trait RemoteSigner: Send + Sync {
+    /// Returns the raw signature of `message` with `algorithm`, the same bytes
+    /// [compute_signature](fn.compute_signature.html) would give for a local key.
+    /// Return `ErrorKind::InvalidAlgorithm` for algorithms the key can't be used with.
+    fn sign(&self, message: &[u8], algorithm: Algorithm) -> Result<Vec<u8>>;
+}
+
+/// A shared [RemoteSigner](trait.RemoteSigner.html), as held by `EncodingKey::Remote`.
+/// Two of them are only equal if they are the same signer.
+#[derive(Clone)]
+pub struct RemoteSigningKey(pub Arc<dyn RemoteSigner>);
+
+impl fmt::Debug for RemoteSigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("RemoteSigningKey")
+    }
+}
+
+impl PartialEq for RemoteSigningKey {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Verifies signatures with a key that can't be loaded in the process, like one kept in a KMS or
+/// an enclave. Use it with `DecodingKey::from_remote_verifier`, the claims are still validated
+/// locally.
+pub trait RemoteVerifier: Send + Sync {
+    /// Checks the raw `signature` of `message` with `algorithm`.
+    /// Return `ErrorKind::InvalidAlgorithm` for algorithms the key can't be used with.
+    fn verify(&self, message: &[u8], signature: &[u8], algorithm: Algorithm) -> Result<bool>;
+
+    /// The kind of algorithms the remote key can be used with, `verify` is never called with an
+    /// algorithm of another family
+    fn family(&self) -> AlgorithmFamily;
+}
+
+/// A shared [RemoteVerifier](trait.RemoteVerifier.html), as held by `DecodingKey::Remote`.
+/// Two of them are only equal if they are the same verifier.
+#[derive(Clone)]
+pub struct RemoteVerifyingKey(pub Arc<dyn RemoteVerifier>);
+
+impl fmt::Debug for RemoteVerifyingKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("RemoteVerifyingKey")
+    }
+}
+
+impl PartialEq for RemoteVerifyingKey {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
@@ -1,42 +1,42 @@
 // use ring::{rand, signature};
 use crate::errors::{ErrorKind, Result};
-use crate::serialization::{b64_decode, b64_encode};
+use crate::serialization::b64_decode;
 use crate::{errors, Algorithm};
-use rsa::traits::SignatureScheme;
-use rsa::{pss::Pss, Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey};
+use rand::RngCore;
+use rsa::traits::{PublicKeyParts, SignatureScheme};
+use rsa::{pss::Pss, BigUint, Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey};
 use sha2::{Digest, Sha256, Sha384, Sha512};
 
-/// The actual RSA signing + encoding
+/// Hashes the message with the digest used by `alg`.
+/// The hash is written to a buffer on the stack, 64 bytes fits the largest one (SHA-512), which
+/// saves a heap allocation on every call.
+fn digest<'a>(alg: Algorithm, message: &[u8], buffer: &'a mut [u8; 64]) -> Result<&'a [u8]> {
+    fn digest_into<D: Digest>(message: &[u8], buffer: &mut [u8; 64]) -> usize {
+        let hash = D::digest(message);
+        buffer[..hash.len()].copy_from_slice(&hash);
+        hash.len()
+    }
+
+    let len = match alg {
+        Algorithm::RS256 | Algorithm::PS256 => digest_into::<Sha256>(message, buffer),
+        Algorithm::RS384 | Algorithm::PS384 => digest_into::<Sha384>(message, buffer),
+        Algorithm::RS512 | Algorithm::PS512 => digest_into::<Sha512>(message, buffer),
+        _ => return Err(ErrorKind::InvalidAlgorithmName.into()),
+    };
+    Ok(&buffer[..len])
+}
+
+/// The actual RSA signing
 /// The key needs to be in binary DER-encoded ASN.1 format
 /// Taken from Ring doc https://briansmith.org/rustdoc/ring/signature/index.html
-pub(crate) fn sign(alg: Algorithm, key: &RsaPrivateKey, message: &str) -> Result<String> {
-    let digest: Vec<u8> = match alg {
-        // PaddingScheme::OAEP {digest, ..} => {
-        //     digest.update(message.as_bytes());
-        //     digest.finalize_reset()
-        // },
-        // None => message.as_bytes().into(),
-        Algorithm::RS256 | Algorithm::PS256 => {
-            let mut hasher = Sha256::new();
-            hasher.update(message.as_bytes());
-            let d = hasher.finalize();
-            d.as_slice().to_vec()
-        }
-        Algorithm::RS384 | Algorithm::PS384 => {
-            let mut hasher = Sha384::new();
-            hasher.update(message.as_bytes());
-            let d = hasher.finalize();
-            d.as_slice().to_vec()
-        }
-        Algorithm::RS512 | Algorithm::PS512 => {
-            let mut hasher = Sha512::new();
-            hasher.update(message.as_bytes());
-            let d = hasher.finalize();
-            d.as_slice().to_vec()
-        }
-        _ => unimplemented!(),
-    };
+pub(crate) fn sign(alg: Algorithm, key: &RsaPrivateKey, message: &[u8]) -> Result<Vec<u8>> {
+    let mut buffer = [0; 64];
+    let digest = digest(alg, message, &mut buffer)?;
+    sign_digest(alg, key, digest)
+}
 
+/// Signs a message already hashed with the digest used by `alg`
+pub(crate) fn sign_digest(alg: Algorithm, key: &RsaPrivateKey, digest: &[u8]) -> Result<Vec<u8>> {
     let signatures_scheme_pkcs = match alg {
         Algorithm::RS256 => Some(Pkcs1v15Sign::new::<Sha256>()),
         Algorithm::RS384 => Some(Pkcs1v15Sign::new::<Sha384>()),
@@ -55,15 +55,15 @@ pub(crate) fn sign(alg: Algorithm, key: &RsaPrivateKey, message: &str) -> Result
 
     let signature = if let Some(signatures_scheme) = signatures_scheme_pkcs {
         // In versions pre 1.2.0, signatures did not use RNG.
-        signatures_scheme.sign(Some(&mut rng), key, &digest).expect("failed to sign pkcs")
+        signatures_scheme.sign(Some(&mut rng), key, digest).expect("failed to sign pkcs")
     } else if let Some(signatures_scheme) = signatures_scheme_pss {
         // PSS requires signing with RNG,otherwise it errors at runtime.
-        signatures_scheme.sign(Some(&mut rng), key, &digest).expect("failed to sign pss")
+        signatures_scheme.sign(Some(&mut rng), key, digest).expect("failed to sign pss")
     } else {
         return Err(ErrorKind::InvalidAlgorithmName.into());
     };
 
-    Ok(b64_encode(&signature))
+    Ok(signature)
 }
 
 /// Checks that a signature is valid based on the (n, e) RSA pubkey components
@@ -73,38 +73,21 @@ pub(crate) fn verify(
     message: &str,
     key: &RsaPublicKey,
 ) -> Result<bool> {
-    let digest: Vec<u8> = match alg {
-        // PaddingScheme::OAEP {digest, ..} => {
-        //     digest.update(message.as_bytes());
-        //     digest.finalize_reset()
-        // },
-        // None => message.as_bytes().into(),
-        Algorithm::RS256 | Algorithm::PS256 => {
-            let mut hasher = Sha256::new();
-            hasher.update(message.as_bytes());
-            let d = hasher.finalize();
-            d.iter().copied().collect()
-        }
-        Algorithm::RS384 | Algorithm::PS384 => {
-            let mut hasher = Sha384::new();
-            hasher.update(message.as_bytes());
-            let d = hasher.finalize();
-            d.iter().copied().collect()
-        }
-        Algorithm::RS512 | Algorithm::PS512 => {
-            let mut hasher = Sha512::new();
-            hasher.update(message.as_bytes());
-            let d = hasher.finalize();
-            d.iter().copied().collect()
-        }
-        // Algorithm::PS256 | Algorithm::PS384 | Algorithm::PS512 => {
-        //     message.as_bytes().to_vec()
-        // }
-        _ => unimplemented!(),
-    };
+    let mut buffer = [0; 64];
+    let digest = digest(alg, message.as_bytes(), &mut buffer)?;
 
-    let signature_bytes = b64_decode(signature)?;
+    let signature_bytes =
+        b64_decode(signature).map_err(|_| errors::new_error(ErrorKind::InvalidSignature))?;
+    verify_digest(alg, &signature_bytes, digest, key)
+}
 
+/// Checks a raw signature of a message already hashed with the digest used by `alg`
+pub(crate) fn verify_digest(
+    alg: Algorithm,
+    signature_bytes: &[u8],
+    digest: &[u8],
+    key: &RsaPublicKey,
+) -> Result<bool> {
     let signatures_scheme_pkcs = match alg {
         Algorithm::RS256 => Some(Pkcs1v15Sign::new::<Sha256>()),
         Algorithm::RS384 => Some(Pkcs1v15Sign::new::<Sha384>()),
@@ -121,11 +104,11 @@ pub(crate) fn verify(
 
     if let Some(signatures_scheme) = signatures_scheme_pkcs {
         signatures_scheme
-            .verify(key, &digest, &signature_bytes)
+            .verify(key, digest, signature_bytes)
             .map_err(|_| errors::new_error(ErrorKind::InvalidSignature))?;
     } else if let Some(signatures_scheme) = signatures_scheme_pss {
         signatures_scheme
-            .verify(key, &digest, &signature_bytes)
+            .verify(key, digest, signature_bytes)
             .map_err(|_| errors::new_error(ErrorKind::InvalidSignature))?;
     } else {
         return Err(ErrorKind::InvalidAlgorithmName.into());
@@ -133,3 +116,138 @@ pub(crate) fn verify(
 
     Ok(true)
 }
+
+/// The hash used by the MGF1 mask generation function of RSASSA-PSS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mgf1Hash {
+    /// MGF1 with SHA-256, the JWA choice for PS256
+    Sha256,
+    /// MGF1 with SHA-384, the JWA choice for PS384
+    Sha384,
+    /// MGF1 with SHA-512, the JWA choice for PS512
+    Sha512,
+}
+
+impl Mgf1Hash {
+    /// Xors `out` with the MGF1 mask generated from `seed`, see
+    /// [RFC8017#B.2.1](https://tools.ietf.org/html/rfc8017#appendix-B.2.1)
+    fn mask(self, out: &mut [u8], seed: &[u8]) {
+        fn mask_with<D: Digest>(out: &mut [u8], seed: &[u8]) {
+            for (counter, chunk) in out.chunks_mut(<D as Digest>::output_size()).enumerate() {
+                let mask = D::new()
+                    .chain_update(seed)
+                    .chain_update((counter as u32).to_be_bytes())
+                    .finalize();
+                chunk.iter_mut().zip(mask).for_each(|(byte, mask)| *byte ^= mask);
+            }
+        }
+
+        match self {
+            Mgf1Hash::Sha256 => mask_with::<Sha256>(out, seed),
+            Mgf1Hash::Sha384 => mask_with::<Sha384>(out, seed),
+            Mgf1Hash::Sha512 => mask_with::<Sha512>(out, seed),
+        }
+    }
+}
+
+/// `H = Hash(0x00 * 8 || mHash || salt)` of EMSA-PSS
+fn pss_hash(alg: Algorithm, m_hash: &[u8], salt: &[u8]) -> Result<Vec<u8>> {
+    let mut buffer = [0; 64];
+    let input = [&[0; 8], m_hash, salt].concat();
+    Ok(digest(alg, &input, &mut buffer)?.to_vec())
+}
+
+/// PSS signing like `sign`, but with the given MGF1 hash instead of the message digest.
+/// The salt is as long as the digest, as JWA requires. EMSA-PSS is done here as the `rsa`
+/// crate always uses the message digest for MGF1, see
+/// [RFC8017#9.1.1](https://tools.ietf.org/html/rfc8017#section-9.1.1).
+pub(crate) fn sign_pss_with_mgf1(
+    alg: Algorithm,
+    mgf1: Mgf1Hash,
+    key: &RsaPrivateKey,
+    message: &[u8],
+) -> Result<Vec<u8>> {
+    let mut buffer = [0; 64];
+    let m_hash = digest(alg, message, &mut buffer)?;
+    let h_len = m_hash.len();
+    let em_bits = key.n().bits() - 1;
+    // usize::div_ceil needs Rust 1.73, newer than the MSRV
+    #[allow(clippy::manual_div_ceil)]
+    let em_len = (em_bits + 7) / 8;
+    if em_len < 2 * h_len + 2 {
+        return Err(ErrorKind::InvalidRsaKey.into());
+    }
+
+    let mut rng = rand::thread_rng();
+    let mut salt = vec![0; h_len];
+    rng.fill_bytes(&mut salt);
+    let h = pss_hash(alg, m_hash, &salt)?;
+
+    // EM = maskedDB || H || 0xbc, with DB = PS || 0x01 || salt
+    let mut em = vec![0; em_len];
+    let db_len = em_len - h_len - 1;
+    em[db_len - h_len - 1] = 0x01;
+    em[db_len - h_len..db_len].copy_from_slice(&salt);
+    mgf1.mask(&mut em[..db_len], &h);
+    em[0] &= 0xFF >> (8 * em_len - em_bits);
+    em[db_len..em_len - 1].copy_from_slice(&h);
+    em[em_len - 1] = 0xBC;
+
+    let signature =
+        rsa::hazmat::rsa_decrypt_and_check(key, Some(&mut rng), &BigUint::from_bytes_be(&em))
+            .map_err(|_| errors::new_error(ErrorKind::InvalidRsaKey))?;
+    let signature = signature.to_bytes_be();
+    let mut padded = vec![0; key.size() - signature.len()];
+    padded.extend_from_slice(&signature);
+    Ok(padded)
+}
+
+/// Checks a signature made by `sign_pss_with_mgf1`, see
+/// [RFC8017#9.1.2](https://tools.ietf.org/html/rfc8017#section-9.1.2)
+pub(crate) fn verify_pss_with_mgf1(
+    alg: Algorithm,
+    mgf1: Mgf1Hash,
+    signature: &str,
+    message: &str,
+    key: &RsaPublicKey,
+) -> Result<bool> {
+    let signature =
+        b64_decode(signature).map_err(|_| errors::new_error(ErrorKind::InvalidSignature))?;
+    let mut buffer = [0; 64];
+    let m_hash = digest(alg, message.as_bytes(), &mut buffer)?;
+    let h_len = m_hash.len();
+    let em_bits = key.n().bits() - 1;
+    // usize::div_ceil needs Rust 1.73, newer than the MSRV
+    #[allow(clippy::manual_div_ceil)]
+    let em_len = (em_bits + 7) / 8;
+
+    let s = BigUint::from_bytes_be(&signature);
+    if signature.len() != key.size() || &s >= key.n() || em_len < 2 * h_len + 2 {
+        return Ok(false);
+    }
+    let m = rsa::hazmat::rsa_encrypt(key, &s)
+        .map_err(|_| errors::new_error(ErrorKind::InvalidSignature))?
+        .to_bytes_be();
+    if m.len() > em_len {
+        return Ok(false);
+    }
+    let mut em = vec![0; em_len - m.len()];
+    em.extend_from_slice(&m);
+
+    let db_len = em_len - h_len - 1;
+    let top_bits = 0xFF >> (8 * em_len - em_bits);
+    if em[em_len - 1] != 0xBC || em[0] & !top_bits != 0 {
+        return Ok(false);
+    }
+    let h = em[db_len..em_len - 1].to_vec();
+    let db = &mut em[..db_len];
+    mgf1.mask(db, &h);
+    db[0] &= top_bits;
+
+    let (padding, salt) = db.split_at(db_len - h_len);
+    let (zeros, one) = padding.split_at(padding.len() - 1);
+    if zeros.iter().any(|&byte| byte != 0) || one != [0x01] {
+        return Ok(false);
+    }
+    Ok(super::bytes_eq(&pss_hash(alg, m_hash, salt)?, &h))
+}
@@ -0,0 +1,196 @@
+use ::rsa::{RsaPrivateKey, RsaPublicKey};
+use hmac::Mac;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use super::{bytes_eq, rsa, HmacSha256, HmacSha384, HmacSha512};
+#[cfg(feature = "sha3")]
+use super::{HmacSha3_256, HmacSha3_384, HmacSha3_512};
+use crate::algorithms::Algorithm;
+use crate::decoding::DecodingKey;
+use crate::encoding::EncodingKey;
+use crate::errors::{ErrorKind, Result};
+
+/// What has to be computed over the signing input: a MAC for HMAC, a digest for RSA
+enum State {
+    HmacSha256(HmacSha256),
+    HmacSha384(HmacSha384),
+    HmacSha512(HmacSha512),
+    #[cfg(feature = "sha3")]
+    HmacSha3_256(HmacSha3_256),
+    #[cfg(feature = "sha3")]
+    HmacSha3_384(HmacSha3_384),
+    #[cfg(feature = "sha3")]
+    HmacSha3_512(HmacSha3_512),
+    Sha256(Sha256),
+    Sha384(Sha384),
+    Sha512(Sha512),
+}
+
+impl State {
+    fn hmac(alg: Algorithm, key: &[u8]) -> Result<Self> {
+        Ok(match alg {
+            Algorithm::HS256 => State::HmacSha256(HmacSha256::new_from_slice(key).unwrap()),
+            Algorithm::HS384 => State::HmacSha384(HmacSha384::new_from_slice(key).unwrap()),
+            Algorithm::HS512 => State::HmacSha512(HmacSha512::new_from_slice(key).unwrap()),
+            #[cfg(feature = "sha3")]
+            Algorithm::HS3_256 => State::HmacSha3_256(HmacSha3_256::new_from_slice(key).unwrap()),
+            #[cfg(feature = "sha3")]
+            Algorithm::HS3_384 => State::HmacSha3_384(HmacSha3_384::new_from_slice(key).unwrap()),
+            #[cfg(feature = "sha3")]
+            Algorithm::HS3_512 => State::HmacSha3_512(HmacSha3_512::new_from_slice(key).unwrap()),
+            _ => return Err(ErrorKind::InvalidAlgorithm.into()),
+        })
+    }
+
+    fn rsa(alg: Algorithm) -> Result<Self> {
+        Ok(match alg {
+            Algorithm::RS256 | Algorithm::PS256 => State::Sha256(Sha256::new()),
+            Algorithm::RS384 | Algorithm::PS384 => State::Sha384(Sha384::new()),
+            Algorithm::RS512 | Algorithm::PS512 => State::Sha512(Sha512::new()),
+            _ => return Err(ErrorKind::InvalidAlgorithm.into()),
+        })
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            State::HmacSha256(mac) => mac.update(data),
+            State::HmacSha384(mac) => mac.update(data),
+            State::HmacSha512(mac) => mac.update(data),
+            #[cfg(feature = "sha3")]
+            State::HmacSha3_256(mac) => mac.update(data),
+            #[cfg(feature = "sha3")]
+            State::HmacSha3_384(mac) => mac.update(data),
+            #[cfg(feature = "sha3")]
+            State::HmacSha3_512(mac) => mac.update(data),
+            State::Sha256(hasher) => hasher.update(data),
+            State::Sha384(hasher) => hasher.update(data),
+            State::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    /// The MAC or the digest of everything given to `update`
+    fn finalize(self) -> Vec<u8> {
+        match self {
+            State::HmacSha256(mac) => mac.finalize().into_bytes().to_vec(),
+            State::HmacSha384(mac) => mac.finalize().into_bytes().to_vec(),
+            State::HmacSha512(mac) => mac.finalize().into_bytes().to_vec(),
+            #[cfg(feature = "sha3")]
+            State::HmacSha3_256(mac) => mac.finalize().into_bytes().to_vec(),
+            #[cfg(feature = "sha3")]
+            State::HmacSha3_384(mac) => mac.finalize().into_bytes().to_vec(),
+            #[cfg(feature = "sha3")]
+            State::HmacSha3_512(mac) => mac.finalize().into_bytes().to_vec(),
+            State::Sha256(hasher) => hasher.finalize().to_vec(),
+            State::Sha384(hasher) => hasher.finalize().to_vec(),
+            State::Sha512(hasher) => hasher.finalize().to_vec(),
+        }
+    }
+}
+
+/// Verifies a signature over a signing input given in chunks, so large payloads don't have to
+/// be in one contiguous buffer. It gives the same result as [verify](fn.verify.html).
+///
+/// ```rust
+/// use jsonwebtoken_rustcrypto::crypto::{compute_signature, Verifier};
+/// use jsonwebtoken_rustcrypto::{Algorithm, DecodingKey, EncodingKey};
+///
+/// let signature = compute_signature(b"header.payload", &EncodingKey::from_hmac_secret(b"secret"), Algorithm::HS256).unwrap();
+/// let valid = Verifier::new(&DecodingKey::from_hmac_secret(b"secret"), Algorithm::HS256)
+///     .unwrap()
+///     .update(b"header.")
+///     .update(b"payload")
+///     .verify(&signature)
+///     .unwrap();
+/// assert!(valid);
+/// ```
+pub struct Verifier<'a> {
+    alg: Algorithm,
+    state: State,
+    rsa_key: Option<&'a RsaPublicKey>,
+}
+
+impl<'a> Verifier<'a> {
+    /// Starts a verification with the given key, `ErrorKind::InvalidAlgorithm` is returned if it
+    /// can't be used with `alg`. Remote keys verify whole messages so they return
+    /// `ErrorKind::UnsupportedKeyType`.
+    pub fn new(key: &'a DecodingKey, alg: Algorithm) -> Result<Self> {
+        match key {
+            DecodingKey::Hmac(secret) => {
+                Ok(Verifier { alg, state: State::hmac(alg, secret)?, rsa_key: None })
+            }
+            DecodingKey::Rsa(key) => {
+                Ok(Verifier { alg, state: State::rsa(alg)?, rsa_key: Some(key) })
+            }
+            DecodingKey::Remote(_) => Err(ErrorKind::UnsupportedKeyType.into()),
+        }
+    }
+
+    /// Adds the next chunk of the signing input
+    pub fn update(mut self, data: &[u8]) -> Self {
+        self.state.update(data);
+        self
+    }
+
+    /// Checks `signature`, the raw signature bytes rather than their base64url encoding
+    pub fn verify(self, signature: &[u8]) -> Result<bool> {
+        let computed = self.state.finalize();
+        match self.rsa_key {
+            Some(key) => rsa::verify_digest(self.alg, signature, &computed, key),
+            None => Ok(bytes_eq(&computed, signature)),
+        }
+    }
+}
+
+/// Signs a signing input given in chunks, so large payloads don't have to be in one contiguous
+/// buffer. It gives the same signature as [compute_signature](fn.compute_signature.html).
+///
+/// ```rust
+/// use jsonwebtoken_rustcrypto::crypto::{compute_signature, Signer};
+/// use jsonwebtoken_rustcrypto::{Algorithm, EncodingKey};
+///
+/// let key = EncodingKey::from_hmac_secret(b"secret");
+/// let signature = Signer::new(&key, Algorithm::HS256)
+///     .unwrap()
+///     .update(b"header.")
+///     .update(b"payload")
+///     .finish()
+///     .unwrap();
+/// assert_eq!(signature, compute_signature(b"header.payload", &key, Algorithm::HS256).unwrap());
+/// ```
+pub struct Signer<'a> {
+    alg: Algorithm,
+    state: State,
+    rsa_key: Option<&'a RsaPrivateKey>,
+}
+
+impl<'a> Signer<'a> {
+    /// Starts a signature with the given key, `ErrorKind::InvalidAlgorithm` is returned if it
+    /// can't be used with `alg`. Remote keys sign whole messages so they return
+    /// `ErrorKind::UnsupportedKeyType`.
+    pub fn new(key: &'a EncodingKey, alg: Algorithm) -> Result<Self> {
+        match key {
+            EncodingKey::Hmac(secret) => {
+                Ok(Signer { alg, state: State::hmac(alg, secret)?, rsa_key: None })
+            }
+            EncodingKey::Rsa(key) => {
+                Ok(Signer { alg, state: State::rsa(alg)?, rsa_key: Some(key) })
+            }
+            EncodingKey::Remote(_) => Err(ErrorKind::UnsupportedKeyType.into()),
+        }
+    }
+
+    /// Adds the next chunk of the signing input
+    pub fn update(mut self, data: &[u8]) -> Self {
+        self.state.update(data);
+        self
+    }
+
+    /// Returns the raw signature bytes, base64url encode them to get a JWS signature
+    pub fn finish(self) -> Result<Vec<u8>> {
+        let computed = self.state.finalize();
+        match self.rsa_key {
+            Some(key) => rsa::sign_digest(self.alg, key, &computed),
+            None => Ok(computed),
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::algorithms::Algorithm;
+use crate::errors::{new_error, ErrorKind, Result};
+use crate::header::Header;
+use crate::serialization::{
+    b64_decode, b64_decode_json, b64_encode, b64_encode_part, from_jwt_payload,
+};
+use crate::validation::{validate, Validation};
+
+/// A signing algorithm that isn't built into this crate, for example an experimental one.
+///
+/// The same value signs and verifies, so it holds whatever key material it needs.
+pub trait JwsAlgorithm: Send + Sync {
+    /// The `alg` header value of tokens using this algorithm
+    fn jwa_name(&self) -> &str;
+
+    /// Signs the `header.claims` message, returning the raw signature bytes
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>>;
+
+    /// Whether `signature` is a valid signature of the `header.claims` message
+    fn verify(&self, message: &[u8], signature: &[u8]) -> Result<bool>;
+}
+
+/// The custom algorithms accepted by [decode](fn.decode.html), looked up by their `alg` name.
+#[derive(Default)]
+pub struct AlgorithmRegistry {
+    algorithms: Vec<Box<dyn JwsAlgorithm>>,
+}
+
+impl AlgorithmRegistry {
+    /// Returns an empty registry
+    pub fn new() -> Self {
+        AlgorithmRegistry::default()
+    }
+
+    /// Adds an algorithm, replacing any registered one with the same name.
+    ///
+    /// Errors with `InvalidAlgorithmName` if the name is the one of a built-in algorithm, those
+    /// have to go through the regular `decode`.
+    pub fn register<A: JwsAlgorithm + 'static>(&mut self, algorithm: A) -> Result<()> {
+        let name = algorithm.jwa_name();
+        if name.parse::<Algorithm>().is_ok() {
+            return Err(new_error(ErrorKind::InvalidAlgorithmName));
+        }
+        self.algorithms.retain(|registered| registered.jwa_name() != name);
+        self.algorithms.push(Box::new(algorithm));
+        Ok(())
+    }
+
+    /// Finds the algorithm registered under that name
+    pub fn get(&self, name: &str) -> Option<&dyn JwsAlgorithm> {
+        self.algorithms.iter().find(|alg| alg.jwa_name() == name).map(|alg| alg.as_ref())
+    }
+}
+
+impl fmt::Debug for AlgorithmRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.algorithms.iter().map(|alg| alg.jwa_name())).finish()
+    }
+}
+
+/// The return type of a successful call to [decode](fn.decode.html).
+#[derive(Debug)]
+pub struct TokenData<T> {
+    /// The decoded JWT header. It is kept as JSON since `Header::alg` can't hold a custom
+    /// algorithm.
+    pub header: Map<String, Value>,
+    /// The decoded JWT claims
+    pub claims: T,
+}
+
+/// Encode the header and claims given and sign the payload using a custom algorithm.
+/// The `alg` of the header is replaced by the name of the algorithm.
+///
+/// Unencoded payloads (`b64: false`) aren't supported here.
+pub fn encode<T: Serialize>(
+    header: &Header,
+    claims: &T,
+    algorithm: &dyn JwsAlgorithm,
+) -> Result<String> {
+    if header.is_unencoded_payload() {
+        return Err(new_error(ErrorKind::InvalidToken));
+    }
+    let mut header = header.to_value()?;
+    header["alg"] = Value::String(algorithm.jwa_name().to_string());
+
+    let encoded_header = b64_encode_part(&header)?;
+    let encoded_claims = b64_encode_part(claims)?;
+    let message = [encoded_header.as_ref(), encoded_claims.as_ref()].join(".");
+    let signature = b64_encode(&algorithm.sign(message.as_bytes())?);
+
+    Ok([message, signature].join("."))
+}
+
+/// Decode and validate a JWT signed with one of the algorithms of `registry`.
+///
+/// Errors with `InvalidAlgorithm` if its `alg` isn't registered. Only the claims are checked
+/// against `validation`: its algorithms and header checks are about built-in algorithms, and
+/// tokens with `crit` parameters are rejected as they can't be understood here.
+pub fn decode<T: DeserializeOwned>(
+    token: &str,
+    registry: &AlgorithmRegistry,
+    validation: &Validation,
+) -> Result<TokenData<T>> {
+    let mut parts = token.split('.');
+    let (header, claims, signature) = match (parts.next(), parts.next(), parts.next(), parts.next())
+    {
+        (Some(header), Some(claims), Some(signature), None) => (header, claims, signature),
+        _ => return Err(new_error(ErrorKind::InvalidToken)),
+    };
+    let decoded_header: Map<String, Value> = serde_json::from_slice(&b64_decode_json(header)?)?;
+    if decoded_header.contains_key("crit") {
+        return Err(new_error(ErrorKind::InvalidToken));
+    }
+
+    let algorithm = decoded_header
+        .get("alg")
+        .and_then(Value::as_str)
+        .and_then(|name| registry.get(name))
+        .ok_or_else(|| new_error(ErrorKind::InvalidAlgorithm))?;
+    let signature = b64_decode(signature).map_err(|_| new_error(ErrorKind::InvalidSignature))?;
+    let message_len = header.len() + 1 + claims.len();
+    if !algorithm.verify(&token.as_bytes()[..message_len], &signature)? {
+        return Err(new_error(ErrorKind::InvalidSignature));
+    }
+
+    let (claims, claims_map): (T, _) = from_jwt_payload(&b64_decode_json(claims)?)?;
+    validate(&claims_map, validation)?;
+
+    Ok(TokenData { header: decoded_header, claims })
+}
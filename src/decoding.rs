@@ -1,12 +1,21 @@
+use std::ops::Range;
+use std::sync::Arc;
+
 use rsa::RsaPublicKey;
-use serde::de::DeserializeOwned;
+use serde::de::{Deserialize, DeserializeOwned};
+use serde::Serialize;
+use serde_json::{Map, Value};
 
-use crate::crypto::verify;
+use crate::algorithms::{Algorithm, AlgorithmFamily};
+use crate::crypto::{verify, RemoteVerifier, RemoteVerifyingKey, RsaKeyPolicy};
 use crate::errors::{new_error, ErrorKind, Result};
 use crate::header::Header;
+use crate::jwk::{JsonWebKeyTypes, JWK};
 // use crate::pem::decoder::PemEncodedKey;
-use crate::serialization::from_jwt_part_claims;
-use crate::validation::{validate, Validation};
+use crate::serialization::{
+    b64_decode, b64_decode_json, b64_decode_padding_indifferent, from_jwt_payload,
+};
+use crate::validation::{validate, validate_header, validate_with_grace, Validation};
 
 use base64::{engine::general_purpose::STANDARD, Engine};
 /// The return type of a successful call to [decode](fn.decode.html).
@@ -16,6 +25,25 @@ pub struct TokenData<T> {
     pub header: Header,
     /// The decoded JWT claims
     pub claims: T,
+    /// Whether the token was signed with one of `Validation::discouraged_algorithms`
+    pub used_discouraged_algorithm: bool,
+    /// Whether the token has expired but was accepted thanks to `Validation::expiry_grace`
+    pub expired_but_in_grace: bool,
+}
+
+impl<T> TokenData<T> {
+    /// The decoded header, for tools editing it before signing the claims again
+    pub fn header_mut(&mut self) -> &mut Header {
+        &mut self.header
+    }
+}
+
+impl<T: Serialize> TokenData<T> {
+    /// Run the claims validation of `validation` again on the decoded claims, for example to check
+    /// them against another audience. The signature isn't verified again.
+    pub fn verify_claims(&self, validation: &Validation) -> Result<()> {
+        validate(&serde_json::to_value(&self.claims)?, validation)
+    }
 }
 
 /// Takes the result of a rsplit and ensure we only get 2 parts
@@ -30,12 +58,23 @@ macro_rules! expect_two {
     }};
 }
 
+/// JWE compact serialization uses 5 parts, which would otherwise fail with a confusing
+/// base64 or JSON error when handed to a JWS decoding function
+fn reject_jwe(token: &str) -> Result<()> {
+    if token.split('.').count() == 5 {
+        return Err(new_error(ErrorKind::UnexpectedJwe));
+    }
+    Ok(())
+}
+
 /// All the different kind of keys we can use to decode a JWT
 /// This key can be re-used so make sure you only initialize it once if you can for better performance
 #[derive(Debug, Clone, PartialEq)]
 pub enum DecodingKey {
     Hmac(Vec<u8>),
     Rsa(rsa::RsaPublicKey),
+    /// A key that verifies out of process, see [from_remote_verifier](#method.from_remote_verifier)
+    Remote(RemoteVerifyingKey),
     // EcPkcs8(Vec<u8>),
 }
 
@@ -50,13 +89,123 @@ impl DecodingKey {
         Ok(DecodingKey::Hmac(STANDARD.decode(secret)?))
     }
 
+    /// If you have a base64url HMAC secret (padded or not), use that.
+    pub fn from_hmac_base64_secret(secret: &str) -> Result<Self> {
+        Ok(DecodingKey::Hmac(b64_decode_padding_indifferent(secret)?))
+    }
+
     pub fn from_rsa(key: rsa::RsaPublicKey) -> Result<Self> {
         Ok(DecodingKey::Rsa(key))
     }
 
+    /// Like [from_rsa](#method.from_rsa), but the key size has to be allowed by `policy`
+    pub fn from_rsa_with_policy(key: rsa::RsaPublicKey, policy: &RsaKeyPolicy) -> Result<Self> {
+        policy.check(&key)?;
+        DecodingKey::from_rsa(key)
+    }
+
+    /// If you have a DER encoded SubjectPublicKeyInfo, use this.
+    ///
+    /// The key type is detected from the algorithm OID. Only RSA keys are supported, EC and
+    /// Ed25519 keys return `ErrorKind::UnsupportedKeyType`.
+    pub fn from_spki_der(der: &[u8]) -> Result<Self> {
+        use rsa::pkcs8::{spki::SubjectPublicKeyInfoRef, DecodePublicKey};
+
+        let spki = SubjectPublicKeyInfoRef::try_from(der)
+            .map_err(|_| new_error(ErrorKind::InvalidKeyFormat))?;
+        if spki.algorithm.oid != rsa::pkcs1::ALGORITHM_OID {
+            return Err(new_error(ErrorKind::UnsupportedKeyType));
+        }
+        let key = RsaPublicKey::from_public_key_der(der)
+            .map_err(|_| new_error(ErrorKind::InvalidRsaKey))?;
+        Ok(DecodingKey::Rsa(key))
+    }
+
+    /// If you have a DER encoded PKCS#1 RSA public key in standard base64, e.g. from an
+    /// environment variable, use this. Surrounding whitespace is ignored.
+    pub fn from_rsa_der_base64(der: &str) -> Result<Self> {
+        use rsa::pkcs1::DecodeRsaPublicKey;
+
+        let key = RsaPublicKey::from_pkcs1_der(&STANDARD.decode(der.trim())?)
+            .map_err(|_| new_error(ErrorKind::InvalidRsaKey))?;
+        Ok(DecodingKey::Rsa(key))
+    }
+
+    /// Like [from_spki_der](#method.from_spki_der), with the DER in standard base64.
+    /// Surrounding whitespace is ignored.
+    pub fn from_spki_der_base64(der: &str) -> Result<Self> {
+        DecodingKey::from_spki_der(&STANDARD.decode(der.trim())?)
+    }
+
+    /// If you already parsed a PEM file with the `pem` crate, use this.
+    ///
+    /// `PUBLIC KEY` (SubjectPublicKeyInfo) and `RSA PUBLIC KEY` (PKCS#1) blocks are accepted,
+    /// other labels return `ErrorKind::InvalidKeyFormat`. Like
+    /// [from_spki_der](#method.from_spki_der), only RSA keys are supported.
+    #[cfg(feature = "pem")]
+    pub fn from_pem_object(pem: &pem::Pem) -> Result<Self> {
+        use rsa::pkcs1::DecodeRsaPublicKey;
+
+        match pem.tag() {
+            "PUBLIC KEY" => DecodingKey::from_spki_der(pem.contents()),
+            "RSA PUBLIC KEY" => {
+                let key = RsaPublicKey::from_pkcs1_der(pem.contents())
+                    .map_err(|_| new_error(ErrorKind::InvalidRsaKey))?;
+                Ok(DecodingKey::Rsa(key))
+            }
+            _ => Err(new_error(ErrorKind::InvalidKeyFormat)),
+        }
+    }
+
+    /// Delegate the signature checks to `verifier`, for keys kept in a KMS or an enclave. The
+    /// header and claims are still parsed and validated locally.
+    pub fn from_remote_verifier(verifier: Arc<dyn RemoteVerifier>) -> Self {
+        DecodingKey::Remote(RemoteVerifyingKey(verifier))
+    }
+
+    /// The kind of algorithms the key can be used with
+    pub fn family(&self) -> AlgorithmFamily {
+        match self {
+            DecodingKey::Hmac(_) => AlgorithmFamily::Hmac,
+            DecodingKey::Rsa(_) => AlgorithmFamily::Rsa,
+            DecodingKey::Remote(RemoteVerifyingKey(verifier)) => verifier.family(),
+        }
+    }
+
+    /// The one algorithm the key is meant for, if there is one, to pick a `Validation` automatically.
+    ///
+    /// HMAC secrets and RSA keys work with several algorithms so they return `None`, use
+    /// [family](#method.family) instead. Only EC keys (ES256 for P-256) and Ed25519 keys (EdDSA)
+    /// determine their algorithm, and this crate doesn't support them. Remote keys return `None`
+    /// as well.
+    pub fn algorithm_hint(&self) -> Option<Algorithm> {
+        match self {
+            DecodingKey::Hmac(_) | DecodingKey::Rsa(_) | DecodingKey::Remote(_) => None,
+        }
+    }
+
+    /// Build a key from a JWK. RSA keys are always supported, `oct` keys become HMAC secrets but
+    /// only when `allow_symmetric` is set, and return `ErrorKind::UntrustedKey` otherwise: anyone
+    /// able to read a symmetric key can also sign tokens with it, which JWKS consumers usually
+    /// don't expect.
+    pub fn from_jwk(jwk: &JWK, allow_symmetric: bool) -> Result<Self> {
+        match (jwk.kty, &jwk.n, &jwk.e, &jwk.k) {
+            (JsonWebKeyTypes::Rsa, Some(n), Some(e), _) => DecodingKey::from_rsa_components(n, e),
+            (JsonWebKeyTypes::Rsa, _, _, _) => Err(new_error(ErrorKind::InvalidRsaKey)),
+            (JsonWebKeyTypes::OctetSeq, _, _, _) if !allow_symmetric => {
+                Err(new_error(ErrorKind::UntrustedKey))
+            }
+            (JsonWebKeyTypes::OctetSeq, _, _, Some(k)) => Ok(DecodingKey::Hmac(b64_decode(k)?)),
+            (JsonWebKeyTypes::OctetSeq, _, _, None) => Err(new_error(ErrorKind::InvalidHmacSecret)),
+            (JsonWebKeyTypes::Ec, _, _, _) => Err(new_error(ErrorKind::UnsupportedKeyType)),
+        }
+    }
+
     /// Convenience function for JWKS implementors
+    ///
+    /// `n` and `e` are base64url big-endian integers, leading zero bytes are ignored. Small odd
+    /// exponents like 3 (`"Aw"`) are accepted, 0 and 1 return `ErrorKind::InvalidKeyFormat`.
     pub fn from_rsa_components(n: &str, e: &str) -> Result<Self> {
-        use crate::serialization::b64_decode;
         let n = rsa::BigUint::from_bytes_be(&b64_decode(n)?);
         let e = rsa::BigUint::from_bytes_be(&b64_decode(e)?);
         Ok(DecodingKey::Rsa(
@@ -69,6 +218,25 @@ impl DecodingKey {
 ///
 /// If the token or its signature is invalid or the claims fail validation, it will return an error.
 ///
+/// When several things are wrong, the error of the first failing step is returned, in this order:
+/// 1. the token shape: `UnexpectedJwe` for encrypted tokens, `InvalidToken` if a part is missing
+/// 2. the header: `Base64`, `Utf8` then `Json` if it can't be decoded
+/// 3. the header algorithm: `InvalidAlgorithm` if it isn't allowed by `validation`
+/// 4. the other header checks of `validation`, such as the `kid`
+/// 5. the signature: `InvalidSignature`, including when it isn't valid base64
+/// 6. the claims: `Base64`, `Utf8` then `Json` if they can't be decoded
+/// 7. the claims validation: required claims, `exp`, `nbf`, their consistency, `iss`, `sub`, `aud`,
+///    `azp`, the scopes then the custom validator
+///
+/// The claims are never looked at before the signature is verified, unless
+/// `Validation::uniform_errors` is set: then the claims validation runs even if the header or
+/// the signature are wrong, and any failure is an `InvalidToken`, so a bad signature can't be told
+/// apart from bad claims. The claims checks themselves still stop at the first failure, unless
+/// `Validation::collect_all_errors` is set.
+///
+/// Signatures are compared in constant time, but the rest of the decoding isn't, and the time
+/// taken can still depend on the length and content of the token.
+///
 /// ```rust
 /// use serde::{Deserialize, Serialize};
 /// use jsonwebtoken_rustcrypto::{decode, DecodingKey, Validation, Algorithm};
@@ -88,22 +256,229 @@ pub fn decode<T: DeserializeOwned>(
     key: &DecodingKey,
     validation: &Validation,
 ) -> Result<TokenData<T>> {
+    decode_and_validate(token, Some(key), validation).map(|(token_data, _)| token_data)
+}
+
+/// Decode and validate a JWT like [decode](fn.decode.html), returning the raw claims as well,
+/// for example to log the fields the typed claims don't have. The payload is only parsed once
+/// for both.
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use jsonwebtoken_rustcrypto::{decode_with_raw, DecodingKey, Validation, Algorithm};
+///
+/// #[derive(Debug, Serialize, Deserialize)]
+/// struct Claims {
+///    sub: String,
+/// }
+///
+/// let token = "a.jwt.token".to_string();
+/// let token_and_raw = decode_with_raw::<Claims>(&token, &DecodingKey::from_hmac_secret("secret".as_ref()), &Validation::new(Algorithm::HS256));
+/// ```
+pub fn decode_with_raw<T: DeserializeOwned>(
+    token: &str,
+    key: &DecodingKey,
+    validation: &Validation,
+) -> Result<(TokenData<T>, Value)> {
+    decode_and_validate(token, Some(key), validation)
+}
+
+/// The shared implementation of `decode` and `dangerous_insecure_decode_with_validation`, so
+/// both run exactly the same header and claims validation.
+/// The signature is only verified if a key is given.
+/// The claims are also returned untyped, once validated.
+fn decode_and_validate<T: DeserializeOwned>(
+    token: &str,
+    key: Option<&DecodingKey>,
+    validation: &Validation,
+) -> Result<(TokenData<T>, Value)> {
+    let (header, decoded_claims, claims_map, in_grace): (_, T, _, _) = if validation.uniform_errors
+    {
+        let (header, payload, claims_map, in_grace) =
+            decode_and_validate_uniformly(token, key, validation)?;
+        (header, serde_json::from_slice(&payload)?, claims_map, in_grace)
+    } else {
+        let (header, payload) = verify_header_and_signature(token, key, validation)?;
+        let (decoded_claims, claims_map) = from_jwt_payload(&payload)?;
+        let in_grace = validate_with_grace(&claims_map, validation)?;
+        (header, decoded_claims, claims_map, in_grace)
+    };
+
+    let token_data = TokenData {
+        used_discouraged_algorithm: validation.discouraged_algorithms.contains(&header.alg),
+        expired_but_in_grace: in_grace,
+        header,
+        claims: decoded_claims,
+    };
+    Ok((token_data, claims_map))
+}
+
+/// A token that passed validation: its header, payload, claims and whether it is only valid
+/// thanks to `Validation::expiry_grace`
+type ValidatedToken = (Header, Vec<u8>, Value, bool);
+
+/// The checks of `decode` for `Validation::uniform_errors`: the claims are validated even if the
+/// header or the signature are wrong, so every failure goes through the same steps, and is
+/// returned as `ErrorKind::InvalidToken`.
+/// Only the claims map is looked at, the typed claims are deserialized once everything passed.
+fn decode_and_validate_uniformly(
+    token: &str,
+    key: Option<&DecodingKey>,
+    validation: &Validation,
+) -> Result<ValidatedToken> {
+    let generic = |_| new_error(ErrorKind::InvalidToken);
+    reject_jwe(token).map_err(generic)?;
     let (signature, message) = expect_two!(token.rsplitn(2, '.'));
     let (claims, header) = expect_two!(message.rsplitn(2, '.'));
-    let header = Header::from_encoded(header)?;
+    let header = Header::from_encoded(unpadded(header, validation)).map_err(generic)?;
+    let claims = if header.is_unencoded_payload() { claims } else { unpadded(claims, validation) };
+    let signature = unpadded(signature, validation);
+
+    let header_valid = (validation.algorithms.is_empty()
+        || validation.algorithms.contains(&header.alg))
+        & validate_header(&header, validation).is_ok();
+    let signature_valid = match key {
+        Some(key) => verify(signature, message, key, header.alg).unwrap_or(false),
+        None => true,
+    };
+    let claims = decode_payload(&header, claims).and_then(|payload| {
+        let claims_map = Value::Object(serde_json::from_slice(&payload)?);
+        let in_grace = validate_with_grace(&claims_map, validation)?;
+        Ok((payload, claims_map, in_grace))
+    });
+
+    match claims {
+        Ok((payload, claims_map, in_grace)) if header_valid & signature_valid => {
+            Ok((header, payload, claims_map, in_grace))
+        }
+        _ => Err(new_error(ErrorKind::InvalidToken)),
+    }
+}
+
+/// Everything that happens in `decode` before the claims are looked at.
+/// Returns the decoded header and payload.
+fn verify_header_and_signature(
+    token: &str,
+    key: Option<&DecodingKey>,
+    validation: &Validation,
+) -> Result<(Header, Vec<u8>)> {
+    reject_jwe(token)?;
+    let (signature, message) = expect_two!(token.rsplitn(2, '.'));
+    let (claims, header) = expect_two!(message.rsplitn(2, '.'));
+    let header = Header::from_encoded(unpadded(header, validation))?;
+    let claims = if header.is_unencoded_payload() { claims } else { unpadded(claims, validation) };
+    let signature = unpadded(signature, validation);
 
     if !validation.algorithms.is_empty() & !&validation.algorithms.contains(&header.alg) {
         return Err(new_error(ErrorKind::InvalidAlgorithm));
     }
+    validate_header(&header, validation)?;
+
+    if let Some(key) = key {
+        if !verify(signature, message, key, header.alg)? {
+            return Err(new_error(ErrorKind::InvalidSignature));
+        }
+    }
+
+    let payload = decode_payload(&header, claims)?;
+    Ok((header, payload))
+}
+
+/// Removes the `=` padding of a base64url encoded part if `Validation::allow_base64_padding`
+/// is set. The signature is still checked over the parts as they are in the token.
+fn unpadded<'a>(part: &'a str, validation: &Validation) -> &'a str {
+    if !validation.allow_base64_padding {
+        return part;
+    }
+    part.strip_suffix("==").or_else(|| part.strip_suffix('=')).unwrap_or(part)
+}
+
+/// The payload is base64url encoded unless the header says otherwise, see
+/// [RFC7797](https://tools.ietf.org/html/rfc7797).
+/// An unencoded payload is only accepted if `b64` is listed in `crit`, as recipients that
+/// don't understand it would verify the signature over the wrong bytes.
+fn decode_payload(header: &Header, claims: &str) -> Result<Vec<u8>> {
+    if !header.is_unencoded_payload() {
+        return b64_decode_json(claims);
+    }
+    match header.crit {
+        Some(ref crit) if crit.iter().any(|name| name == "b64") => Ok(claims.as_bytes().to_vec()),
+        _ => Err(new_error(ErrorKind::InvalidToken)),
+    }
+}
+
+/// Decode and validate a JWT like [decode](fn.decode.html), but return the exact payload bytes
+/// instead of deserializing them.
+///
+/// ```rust
+/// use jsonwebtoken_rustcrypto::{decode_payload_bytes, DecodingKey, Validation, Algorithm};
+///
+/// let token = "a.jwt.token".to_string();
+/// let payload = decode_payload_bytes(&token, &DecodingKey::from_hmac_secret("secret".as_ref()), &Validation::new(Algorithm::HS256));
+/// ```
+pub fn decode_payload_bytes(
+    token: &str,
+    key: &DecodingKey,
+    validation: &Validation,
+) -> Result<(Header, Vec<u8>)> {
+    decode_and_validate_payload(token, key, validation)
+        .map(|(header, payload, _)| (header, payload))
+}
 
-    if !verify(signature, message, key, header.alg)? {
-        return Err(new_error(ErrorKind::InvalidSignature));
+/// `decode_payload_bytes`, also returning whether the token is only valid thanks to
+/// `Validation::expiry_grace`
+fn decode_and_validate_payload(
+    token: &str,
+    key: &DecodingKey,
+    validation: &Validation,
+) -> Result<(Header, Vec<u8>, bool)> {
+    if validation.uniform_errors {
+        return decode_and_validate_uniformly(token, Some(key), validation)
+            .map(|(header, payload, _, in_grace)| (header, payload, in_grace));
     }
+    let (header, payload) = verify_header_and_signature(token, Some(key), validation)?;
 
-    let (decoded_claims, claims_map): (T, _) = from_jwt_part_claims(claims)?;
-    validate(&claims_map, validation)?;
+    let claims_map = Value::Object(serde_json::from_slice(&payload)?);
+    let in_grace = validate_with_grace(&claims_map, validation)?;
+
+    Ok((header, payload, in_grace))
+}
+
+/// Decode and validate a JWT like [decode](fn.decode.html), into claims that borrow from the payload.
+///
+/// The decoded payload is stored in `buffer`, which has to outlive the returned claims.
+/// Only strings without JSON escapes can be borrowed, use `Cow<'a, str>` if they may contain some.
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use jsonwebtoken_rustcrypto::{decode_borrowed, DecodingKey, Validation, Algorithm};
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Claims<'a> {
+///    sub: &'a str,
+///    company: &'a str
+/// }
+///
+/// let token = "a.jwt.token".to_string();
+/// let mut buffer = Vec::new();
+/// let token_message = decode_borrowed::<Claims>(&token, &DecodingKey::from_hmac_secret("secret".as_ref()), &Validation::new(Algorithm::HS256), &mut buffer);
+/// ```
+pub fn decode_borrowed<'de, T: Deserialize<'de>>(
+    token: &str,
+    key: &DecodingKey,
+    validation: &Validation,
+    buffer: &'de mut Vec<u8>,
+) -> Result<TokenData<T>> {
+    let (header, payload, in_grace) = decode_and_validate_payload(token, key, validation)?;
+    *buffer = payload;
+    let claims = serde_json::from_slice(buffer)?;
 
-    Ok(TokenData { header, claims: decoded_claims })
+    Ok(TokenData {
+        used_discouraged_algorithm: validation.discouraged_algorithms.contains(&header.alg),
+        expired_but_in_grace: in_grace,
+        header,
+        claims,
+    })
 }
 
 /// Decode a JWT without any signature verification/validations.
@@ -125,18 +500,42 @@ pub fn decode<T: DeserializeOwned>(
 /// let token_message = dangerous_insecure_decode::<Claims>(&token);
 /// ```
 pub fn dangerous_insecure_decode<T: DeserializeOwned>(token: &str) -> Result<TokenData<T>> {
+    reject_jwe(token)?;
     let (_, message) = expect_two!(token.rsplitn(2, '.'));
     let (claims, header) = expect_two!(message.rsplitn(2, '.'));
     let header = Header::from_encoded(header)?;
 
-    let (decoded_claims, _): (T, _) = from_jwt_part_claims(claims)?;
+    let (decoded_claims, _): (T, _) = from_jwt_payload(&decode_payload(&header, claims)?)?;
+
+    Ok(TokenData {
+        header,
+        claims: decoded_claims,
+        used_discouraged_algorithm: false,
+        expired_but_in_grace: false,
+    })
+}
 
-    Ok(TokenData { header, claims: decoded_claims })
+/// Returns the names of the top level claims of a JWT, for example to find out why a required
+/// claim check fails.
+///
+/// NOTE: Nothing is verified or validated, the names come from whoever made the token.
+///
+/// ```rust
+/// use jsonwebtoken_rustcrypto::{claim_names, encode, EncodingKey, Header};
+///
+/// let key = EncodingKey::from_hmac_secret("secret".as_ref());
+/// let token = encode(&Header::default(), &serde_json::json!({"exp": 1, "sub": "b@b.com"}), &key).unwrap();
+/// assert_eq!(claim_names(&token).unwrap(), vec!["exp", "sub"]);
+/// ```
+pub fn claim_names(token: &str) -> Result<Vec<String>> {
+    let claims = dangerous_insecure_decode::<Map<String, Value>>(token)?.claims;
+    Ok(claims.into_iter().map(|(name, _)| name).collect())
 }
 
 /// Decode and validate a JWT without any signature verification.
 ///
 /// If the token is invalid or the claims fail validation, it will return an error.
+/// The validation is exactly the same as [decode](fn.decode.html) does, only the signature check is skipped.
 ///
 /// NOTE: Do not use this unless you know what you are doing! If the token's signature is invalid, it will *not* return an error.
 ///
@@ -158,18 +557,7 @@ pub fn dangerous_insecure_decode_with_validation<T: DeserializeOwned>(
     token: &str,
     validation: &Validation,
 ) -> Result<TokenData<T>> {
-    let (_, message) = expect_two!(token.rsplitn(2, '.'));
-    let (claims, header) = expect_two!(message.rsplitn(2, '.'));
-    let header = Header::from_encoded(header)?;
-
-    if !validation.algorithms.is_empty() & !&validation.algorithms.contains(&header.alg) {
-        return Err(new_error(ErrorKind::InvalidAlgorithm));
-    }
-
-    let (decoded_claims, claims_map): (T, _) = from_jwt_part_claims(claims)?;
-    validate(&claims_map, validation)?;
-
-    Ok(TokenData { header, claims: decoded_claims })
+    decode_and_validate(token, None, validation).map(|(token_data, _)| token_data)
 }
 
 /// Decode a JWT without any signature verification/validations. DEPRECATED.
@@ -183,6 +571,7 @@ pub fn dangerous_unsafe_decode<T: DeserializeOwned>(token: &str) -> Result<Token
 /// Decode a JWT without any signature verification/validations and return its [Header](struct.Header.html).
 ///
 /// If the token has an invalid format (ie 3 parts separated by a `.`), it will return an error.
+/// Encrypted tokens (JWE, 5 parts) are reported with `ErrorKind::UnexpectedJwe`.
 ///
 /// ```rust
 /// use jsonwebtoken_rustcrypto::decode_header;
@@ -191,7 +580,92 @@ pub fn dangerous_unsafe_decode<T: DeserializeOwned>(token: &str) -> Result<Token
 /// let header = decode_header(&token);
 /// ```
 pub fn decode_header(token: &str) -> Result<Header> {
+    reject_jwe(token)?;
     let (_, message) = expect_two!(token.rsplitn(2, '.'));
     let (_, header) = expect_two!(message.rsplitn(2, '.'));
     Header::from_encoded(header)
 }
+
+/// Like [decode_header](fn.decode_header.html), but also returns the family of the header
+/// algorithm, for code routing tokens to different key types.
+///
+/// ```rust
+/// use jsonwebtoken_rustcrypto::decode_header_ext;
+///
+/// let token = "a.jwt.token".to_string();
+/// let header_and_family = decode_header_ext(&token);
+/// ```
+pub fn decode_header_ext(token: &str) -> Result<(Header, AlgorithmFamily)> {
+    let header = decode_header(token)?;
+    let family = header.alg.family();
+    Ok((header, family))
+}
+
+/// Decode the header and payload of a JWT without any signature verification/validations,
+/// returning the payload as text, exactly as it was encoded. Meant for debugging tools showing
+/// what's inside a token, invalid UTF-8 is replaced by `U+FFFD`.
+///
+/// NOTE: Do not use this unless you know what you are doing! Nothing about the token is checked,
+/// the payload could have been written by anyone.
+///
+/// ```rust
+/// use jsonwebtoken_rustcrypto::decode_header_and_preview;
+///
+/// let token = "a.jwt.token".to_string();
+/// let header_and_payload = decode_header_and_preview(&token);
+/// ```
+pub fn decode_header_and_preview(token: &str) -> Result<(Header, String)> {
+    reject_jwe(token)?;
+    let (_, message) = expect_two!(token.rsplitn(2, '.'));
+    let (claims, header) = expect_two!(message.rsplitn(2, '.'));
+    let header = Header::from_encoded(header)?;
+    let payload = decode_payload(&header, claims)?;
+
+    Ok((header, String::from_utf8_lossy(&payload).into_owned()))
+}
+
+/// Split a token and parse its header and claims without any signature verification/validations.
+///
+/// This is the parsing half of [decode](fn.decode.html) on its own, making it a good `cargo fuzz`
+/// target: it must return an error rather than panic for any input.
+///
+/// ```rust
+/// use jsonwebtoken_rustcrypto::try_parse;
+///
+/// assert!(try_parse("not.a.token").is_err());
+/// ```
+pub fn try_parse(token: &str) -> Result<(Header, Value)> {
+    reject_jwe(token)?;
+    let (_, message) = expect_two!(token.rsplitn(2, '.'));
+    let (claims, header) = expect_two!(message.rsplitn(2, '.'));
+    let header = Header::from_encoded(header)?;
+    let claims = serde_json::from_slice(&decode_payload(&header, claims)?)?;
+
+    Ok((header, claims))
+}
+
+/// Returns the byte ranges of the header, payload and signature parts within `token`, split the
+/// same way [decode](fn.decode.html) does, to slice the original token without splitting it again.
+///
+/// NOTE: Only the shape of the token is checked, verify it with `decode` first.
+///
+/// ```rust
+/// use jsonwebtoken_rustcrypto::token_ranges;
+///
+/// let token = "header.payload.signature";
+/// let (header, payload, signature) = token_ranges(token).unwrap();
+/// assert_eq!(&token[payload], "payload");
+/// ```
+pub fn token_ranges(token: &str) -> Result<(Range<usize>, Range<usize>, Range<usize>)> {
+    reject_jwe(token)?;
+    let (signature, message) = expect_two!(token.rsplitn(2, '.'));
+    let (claims, header) = expect_two!(message.rsplitn(2, '.'));
+
+    let payload_start = header.len() + 1;
+    let signature_start = message.len() + 1;
+    Ok((
+        0..header.len(),
+        payload_start..payload_start + claims.len(),
+        signature_start..signature_start + signature.len(),
+    ))
+}
@@ -1,10 +1,17 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use serde::ser::Serialize;
 
 use crate::crypto;
-use crate::errors::Result;
+use crate::crypto::{RemoteSigner, RemoteSigningKey, RsaKeyPolicy};
+use crate::errors::{new_error, ErrorKind, Result};
 use crate::header::Header;
+use crate::jwk::{JsonWebKeyTypes, JWK};
 // use crate::pem::decoder::PemEncodedKey;
-use crate::serialization::b64_encode_part;
+use crate::serialization::{
+    b64_decode, b64_decode_padding_indifferent, b64_encode, b64_encode_part, to_canonical_json,
+};
 
 use base64::{engine::general_purpose::STANDARD, Engine};
 
@@ -14,6 +21,8 @@ use base64::{engine::general_purpose::STANDARD, Engine};
 pub enum EncodingKey {
     Hmac(Vec<u8>),
     Rsa(Box<rsa::RsaPrivateKey>),
+    /// A key that signs out of process, see [from_remote](#method.from_remote)
+    Remote(RemoteSigningKey),
     // EcPkcs8(Vec<u8>),
 }
 
@@ -28,11 +37,114 @@ impl EncodingKey {
         Ok(EncodingKey::Hmac(STANDARD.decode(secret)?))
     }
 
+    /// If you have a base64url HMAC secret (padded or not), use that.
+    pub fn from_hmac_base64_secret(secret: &str) -> Result<Self> {
+        Ok(EncodingKey::Hmac(b64_decode_padding_indifferent(secret)?))
+    }
+
     pub fn from_rsa(key: rsa::RsaPrivateKey) -> Result<Self> {
         Ok(EncodingKey::Rsa(Box::new(key)))
     }
+
+    /// Delegate signing to `signer`, for keys kept in a KMS or an HSM. Any algorithm is passed on
+    /// to it, it is up to the signer to reject the ones its key can't be used with.
+    pub fn from_remote(signer: Arc<dyn RemoteSigner>) -> Self {
+        EncodingKey::Remote(RemoteSigningKey(signer))
+    }
+
+    /// Like [from_rsa](#method.from_rsa), but the key size has to be allowed by `policy`
+    pub fn from_rsa_with_policy(key: rsa::RsaPrivateKey, policy: &RsaKeyPolicy) -> Result<Self> {
+        policy.check(&key)?;
+        EncodingKey::from_rsa(key)
+    }
+
+    /// Build a key from a private JWK: an RSA key with its `d` member, `p` and `q` being
+    /// recovered if they are missing, or an `oct` key.
+    /// A public RSA JWK returns `ErrorKind::InvalidRsaKey`, EC keys `ErrorKind::UnsupportedKeyType`.
+    pub fn from_jwk(jwk: &JWK) -> Result<Self> {
+        let uint = |value: &str| b64_decode(value).map(|bytes| rsa::BigUint::from_bytes_be(&bytes));
+        match (jwk.kty, &jwk.n, &jwk.e, &jwk.d) {
+            (JsonWebKeyTypes::Rsa, Some(n), Some(e), Some(d)) => {
+                let primes = match (&jwk.p, &jwk.q) {
+                    (Some(p), Some(q)) => vec![uint(p)?, uint(q)?],
+                    _ => Vec::new(),
+                };
+                let key = rsa::RsaPrivateKey::from_components(uint(n)?, uint(e)?, uint(d)?, primes)
+                    .map_err(|_| new_error(ErrorKind::InvalidRsaKey))?;
+                EncodingKey::from_rsa(key)
+            }
+            (JsonWebKeyTypes::Rsa, _, _, _) => Err(new_error(ErrorKind::InvalidRsaKey)),
+            (JsonWebKeyTypes::OctetSeq, _, _, _) => match jwk.k {
+                Some(ref k) => Ok(EncodingKey::Hmac(b64_decode(k)?)),
+                None => Err(new_error(ErrorKind::InvalidHmacSecret)),
+            },
+            (JsonWebKeyTypes::Ec, _, _, _) => Err(new_error(ErrorKind::UnsupportedKeyType)),
+        }
+    }
+
+    /// If you have a DER encoded PKCS#8 private key, use this.
+    ///
+    /// The key type is detected from the algorithm OID. Only RSA keys are supported, EC and
+    /// Ed25519 keys return `ErrorKind::UnsupportedKeyType`.
+    pub fn from_pkcs8_der(der: &[u8]) -> Result<Self> {
+        use rsa::pkcs8::{DecodePrivateKey, PrivateKeyInfo};
+
+        let info =
+            PrivateKeyInfo::try_from(der).map_err(|_| new_error(ErrorKind::InvalidKeyFormat))?;
+        if info.algorithm.oid != rsa::pkcs1::ALGORITHM_OID {
+            return Err(new_error(ErrorKind::UnsupportedKeyType));
+        }
+        let key = rsa::RsaPrivateKey::from_pkcs8_der(der)
+            .map_err(|_| new_error(ErrorKind::InvalidRsaKey))?;
+        Ok(EncodingKey::Rsa(Box::new(key)))
+    }
+
+    /// If you have a DER encoded PKCS#1 RSA private key in standard base64, e.g. from an
+    /// environment variable, use this. Surrounding whitespace is ignored.
+    pub fn from_rsa_der_base64(der: &str) -> Result<Self> {
+        use rsa::pkcs1::DecodeRsaPrivateKey;
+
+        let key = rsa::RsaPrivateKey::from_pkcs1_der(&STANDARD.decode(der.trim())?)
+            .map_err(|_| new_error(ErrorKind::InvalidRsaKey))?;
+        Ok(EncodingKey::Rsa(Box::new(key)))
+    }
+
+    /// If you already parsed a PEM file with the `pem` crate, use this.
+    ///
+    /// `PRIVATE KEY` (PKCS#8) and `RSA PRIVATE KEY` (PKCS#1) blocks are accepted, `EC PRIVATE KEY`
+    /// returns `ErrorKind::UnsupportedKeyType` and other labels `ErrorKind::InvalidKeyFormat`.
+    #[cfg(feature = "pem")]
+    pub fn from_pem_object(pem: &pem::Pem) -> Result<Self> {
+        use rsa::pkcs1::DecodeRsaPrivateKey;
+
+        match pem.tag() {
+            "PRIVATE KEY" => EncodingKey::from_pkcs8_der(pem.contents()),
+            "RSA PRIVATE KEY" => {
+                let key = rsa::RsaPrivateKey::from_pkcs1_der(pem.contents())
+                    .map_err(|_| new_error(ErrorKind::InvalidRsaKey))?;
+                Ok(EncodingKey::Rsa(Box::new(key)))
+            }
+            "EC PRIVATE KEY" => Err(new_error(ErrorKind::UnsupportedKeyType)),
+            _ => Err(new_error(ErrorKind::InvalidKeyFormat)),
+        }
+    }
 }
 
+/// HMAC secrets are wiped when the key is dropped, RSA keys already zeroize themselves.
+#[cfg(feature = "zeroize")]
+impl Drop for EncodingKey {
+    fn drop(&mut self) {
+        use zeroize::Zeroize;
+
+        if let EncodingKey::Hmac(secret) = self {
+            secret.zeroize();
+        }
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for EncodingKey {}
+
 /// Encode the header and claims given and sign the payload using the algorithm from the header and the key.
 /// If the algorithm given is RSA or EC, the key needs to be in the PEM format.
 ///
@@ -55,12 +167,174 @@ impl EncodingKey {
 /// // This will create a JWT using HS256 as algorithm
 /// let token = encode(&Header::default(), &my_claims, &EncodingKey::from_hmac_secret("secret".as_ref())).unwrap();
 /// ```
+///
+/// If the header has `b64` set to `false`, the claims JSON is included as is instead of being
+/// base64url encoded ([RFC7797](https://tools.ietf.org/html/rfc7797)) and `b64` is added to `crit`.
+/// As the token parts are separated by `.`, the claims JSON must not contain any.
+///
+/// The header `alg` has to match the kind of key, signing with an HMAC secret but a `RS256`
+/// header for example returns `ErrorKind::InvalidAlgorithm`.
 pub fn encode<T: Serialize>(header: &Header, claims: &T, key: &EncodingKey) -> Result<String> {
+    encode_raw(header, &serde_json::to_vec(&claims)?, key)
+}
+
+/// Claims with an issued at and an expiry time, that [encode_with_ttl](fn.encode_with_ttl.html)
+/// can fill in. Both are UTC timestamps in seconds.
+pub trait StandardClaims {
+    /// Sets the `iat` claim
+    fn set_iat(&mut self, iat: u64);
+    /// Sets the `exp` claim
+    fn set_exp(&mut self, exp: u64);
+}
+
+/// Like [encode](fn.encode.html), but sets `iat` to now and `exp` to now + `ttl` on the claims
+/// first. The claims keep these values, for callers that need to know when the token expires.
+///
+/// ```rust
+/// use std::time::Duration;
+/// use serde::Serialize;
+/// use jsonwebtoken_rustcrypto::{encode_with_ttl, StandardClaims, Header, EncodingKey};
+///
+/// #[derive(Serialize)]
+/// struct Claims {
+///     sub: String,
+///     iat: u64,
+///     exp: u64,
+/// }
+///
+/// impl StandardClaims for Claims {
+///     fn set_iat(&mut self, iat: u64) {
+///         self.iat = iat;
+///     }
+///     fn set_exp(&mut self, exp: u64) {
+///         self.exp = exp;
+///     }
+/// }
+///
+/// let mut claims = Claims { sub: "b@b.com".to_owned(), iat: 0, exp: 0 };
+/// let key = EncodingKey::from_hmac_secret("secret".as_ref());
+/// let token = encode_with_ttl(&Header::default(), &mut claims, &key, Duration::from_secs(3600)).unwrap();
+/// ```
+pub fn encode_with_ttl<T: Serialize + StandardClaims>(
+    header: &Header,
+    claims: &mut T,
+    key: &EncodingKey,
+    ttl: Duration,
+) -> Result<String> {
+    encode_with_ttl_at(header, claims, key, ttl, SystemTime::now())
+}
+
+/// Like [encode_with_ttl](fn.encode_with_ttl.html), with the current time given, for tests or
+/// tokens that should be valid from another point in time.
+///
+/// A `ttl` so large that `exp` overflows is an `ErrorKind::InvalidToken`, the claims are left
+/// untouched.
+pub fn encode_with_ttl_at<T: Serialize + StandardClaims>(
+    header: &Header,
+    claims: &mut T,
+    key: &EncodingKey,
+    ttl: Duration,
+    now: SystemTime,
+) -> Result<String> {
+    let now = now.duration_since(UNIX_EPOCH).map_err(|_| new_error(ErrorKind::InvalidToken))?;
+    let exp = now.checked_add(ttl).ok_or_else(|| new_error(ErrorKind::InvalidToken))?;
+    claims.set_iat(now.as_secs());
+    claims.set_exp(exp.as_secs());
+    encode(header, claims, key)
+}
+
+/// Signs new claims with the header of an existing token, keeping its `kid`, `typ` and other
+/// fields, for refresh flows. The old token is not verified, only its header is decoded, so
+/// decode it with [decode](fn.decode.html) first if it comes from a client.
+///
+/// ```rust
+/// use jsonwebtoken_rustcrypto::{encode, reissue, Header, EncodingKey};
+///
+/// let key = EncodingKey::from_hmac_secret("secret".as_ref());
+/// let header = Header::new_with_kid(Default::default(), "signing_key");
+/// let token = encode(&header, &serde_json::json!({"sub": "b@b.com", "exp": 1}), &key).unwrap();
+/// let refreshed = reissue(&token, &serde_json::json!({"sub": "b@b.com", "exp": 2}), &key).unwrap();
+/// ```
+pub fn reissue<T: Serialize>(old_token: &str, new_claims: &T, key: &EncodingKey) -> Result<String> {
+    let header = crate::decoding::decode_header(old_token)?;
+    encode(&header, new_claims, key)
+}
+
+/// Like [encode](fn.encode.html), but only for JWTs: the claims must serialize to a JSON object,
+/// otherwise `ErrorKind::InvalidToken` is returned. `encode` accepts any payload, as JWS allows.
+///
+/// ```rust
+/// use jsonwebtoken_rustcrypto::{encode_strict, Header, EncodingKey};
+///
+/// let key = EncodingKey::from_hmac_secret("secret".as_ref());
+/// assert!(encode_strict(&Header::default(), &42u64, &key).is_err());
+/// ```
+pub fn encode_strict<T: Serialize>(
+    header: &Header,
+    claims: &T,
+    key: &EncodingKey,
+) -> Result<String> {
+    let payload = serde_json::to_vec(&claims)?;
+    if payload.first() != Some(&b'{') {
+        return Err(new_error(ErrorKind::InvalidToken));
+    }
+    encode_raw(header, &payload, key)
+}
+
+/// Like [encode](fn.encode.html), but the claims are serialized as canonical JSON: compact, with
+/// the keys of every object sorted. The same claims then give the same token whatever the field
+/// order of the type they come from, which helps producing identical tokens on other platforms.
+/// Numbers are written as `serde_json` does, which isn't always the form RFC 8785 would use.
+///
+/// ```rust
+/// use jsonwebtoken_rustcrypto::{encode_canonical, Header, EncodingKey};
+///
+/// let key = EncodingKey::from_hmac_secret("secret".as_ref());
+/// let token = encode_canonical(&Header::default(), &serde_json::json!({"sub": "b@b.com", "exp": 1}), &key).unwrap();
+/// ```
+pub fn encode_canonical<T: Serialize>(
+    header: &Header,
+    claims: &T,
+    key: &EncodingKey,
+) -> Result<String> {
+    encode_raw(header, &to_canonical_json(claims)?, key)
+}
+
+/// Like [encode](fn.encode.html), but with a payload that is already serialized, which is
+/// signed exactly as given.
+///
+/// ```rust
+/// use jsonwebtoken_rustcrypto::{encode_raw, Header, EncodingKey};
+///
+/// let token = encode_raw(&Header::default(), br#"{"sub":"b@b.com"}"#, &EncodingKey::from_hmac_secret("secret".as_ref())).unwrap();
+/// ```
+pub fn encode_raw(header: &Header, payload: &[u8], key: &EncodingKey) -> Result<String> {
     crypto::validate_matching_key(key, header.alg)?;
+    if header.is_unencoded_payload() {
+        return encode_unencoded_payload(header, payload, key);
+    }
     let encoded_header = b64_encode_part(&header)?;
-    let encoded_claims = b64_encode_part(&claims)?;
+    let encoded_claims = b64_encode(payload);
     let message = [encoded_header.as_ref(), encoded_claims.as_ref()].join(".");
     let signature = crypto::sign(&message, key, header.alg)?;
 
     Ok([message, signature].join("."))
 }
+
+fn encode_unencoded_payload(header: &Header, payload: &[u8], key: &EncodingKey) -> Result<String> {
+    let mut header = header.clone();
+    let crit = header.crit.get_or_insert_with(Vec::new);
+    if !crit.iter().any(|name| name == "b64") {
+        crit.push("b64".to_string());
+    }
+
+    // The payload ends up in the token as is, so it has to be text without any `.`
+    let payload = std::str::from_utf8(payload).map_err(|_| new_error(ErrorKind::InvalidToken))?;
+    if payload.contains('.') {
+        return Err(new_error(ErrorKind::InvalidToken));
+    }
+    let message = [b64_encode_part(&header)?.as_str(), payload].join(".");
+    let signature = crypto::sign(&message, key, header.alg)?;
+
+    Ok([message, signature].join("."))
+}
@@ -7,7 +7,17 @@ pub(crate) fn new_error(kind: ErrorKind) -> Error {
     Error(Box::new(kind))
 }
 
-/// A type alias for `Result<T, jsonwebtoken_rustcrypto::Error>`.
+/// A type alias for `Result<T, jsonwebtoken_rustcrypto::errors::Error>`, also available at the crate root.
+///
+/// ```rust
+/// use jsonwebtoken_rustcrypto::{decode_header, Algorithm, Result};
+///
+/// fn token_algorithm(token: &str) -> Result<Algorithm> {
+///     Ok(decode_header(token)?.alg)
+/// }
+///
+/// assert!(token_algorithm("not a token").is_err());
+/// ```
 pub type Result<T> = result::Result<T, Error>;
 
 /// An error that can occur when encoding/decoding JWTs
@@ -48,6 +58,8 @@ pub enum ErrorKind {
     InvalidAlgorithmName,
     /// When a key is provided with an invalid format
     InvalidKeyFormat,
+    /// When the token has the 5 parts of an encrypted JWE rather than the 3 parts of a signed JWS
+    UnexpectedJwe,
 
     //  JWT Validation errors
     /// When a token’s `exp` claim indicates that it has expired
@@ -60,6 +72,12 @@ pub enum ErrorKind {
     InvalidSubject,
     /// When a token’s nbf claim represents a time in the future
     ImmatureSignature,
+    /// When a claim listed in `Validation::required_spec_claims` is missing, with its name
+    MissingRequiredClaim(String),
+    /// When a token doesn't have all the scopes of `Validation::required_scopes`
+    InsufficientScope,
+    /// When `Validation::custom_validator` rejected the claims, with its message
+    CustomValidation(String),
     /// When the algorithm in the header doesn't match the one passed to `decode` or the encoding/decoding key
     /// used doesn't match the alg requested
     InvalidAlgorithm,
@@ -71,6 +89,10 @@ pub enum ErrorKind {
 
     /// No key matched the conditions and worked successfully
     NoWorkingKey,
+    /// When the key referenced or embedded in the header isn't one the caller allowed
+    UntrustedKey,
+    /// All the claims validation failures, in order, when `Validation::collect_all_errors` is set
+    Multiple(Vec<ErrorKind>),
 
     // 3rd party errors
     /// An error happened when decoding some base64 text
@@ -96,12 +118,18 @@ impl StdError for Error {
             ErrorKind::InvalidAudience => None,
             ErrorKind::InvalidSubject => None,
             ErrorKind::ImmatureSignature => None,
+            ErrorKind::MissingRequiredClaim(_) => None,
+            ErrorKind::InsufficientScope => None,
+            ErrorKind::CustomValidation(_) => None,
             ErrorKind::InvalidAlgorithm => None,
             ErrorKind::InvalidAlgorithmName => None,
             ErrorKind::InvalidKeyFormat => None,
+            ErrorKind::UnexpectedJwe => None,
             ErrorKind::UnsupportedAlgorithm => None,
             ErrorKind::UnsupportedKeyType => None,
             ErrorKind::NoWorkingKey => None,
+            ErrorKind::UntrustedKey => None,
+            ErrorKind::Multiple(_) => None,
             ErrorKind::Base64(ref err) => Some(err),
             ErrorKind::Json(ref err) => Some(err),
             ErrorKind::Utf8(ref err) => Some(err),
@@ -124,10 +152,20 @@ impl fmt::Display for Error {
             | ErrorKind::UnsupportedAlgorithm
             | ErrorKind::UnsupportedKeyType
             | ErrorKind::ImmatureSignature
+            | ErrorKind::InsufficientScope
             | ErrorKind::InvalidAlgorithm
             | ErrorKind::InvalidKeyFormat
+            | ErrorKind::UnexpectedJwe
             | ErrorKind::NoWorkingKey
+            | ErrorKind::UntrustedKey
+            | ErrorKind::Multiple(_)
             | ErrorKind::InvalidAlgorithmName => write!(f, "{:?}", self.0),
+            ErrorKind::MissingRequiredClaim(ref name) => {
+                write!(f, "Missing required claim: {}", name)
+            }
+            ErrorKind::CustomValidation(ref message) => {
+                write!(f, "Custom validation failed: {}", message)
+            }
             ErrorKind::Json(ref err) => write!(f, "JSON error: {}", err),
             ErrorKind::Utf8(ref err) => write!(f, "UTF-8 error: {}", err),
             ErrorKind::Base64(ref err) => write!(f, "Base64 error: {}", err),
@@ -1,22 +1,30 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
 
 use crate::algorithms::Algorithm;
 use crate::errors::Result;
-use crate::serialization::b64_decode;
+use crate::jwk::JWK;
+use crate::serialization::{b64_decode_json, b64_encode};
 
 /// A basic JWT header, the alg defaults to HS256 and typ is automatically
 /// set to `JWT`. All the other fields are optional.
-#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
+///
+/// It always serializes in the same order: `alg` first, then the other registered parameters
+/// in the order of the fields below, then `extras` sorted by name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Header {
+    /// The algorithm used
+    ///
+    /// Defined in [RFC7515#4.1.1](https://tools.ietf.org/html/rfc7515#section-4.1.1).
+    pub alg: Algorithm,
     /// The type of JWS: it can only be "JWT" here
     ///
     /// Defined in [RFC7515#4.1.9](https://tools.ietf.org/html/rfc7515#section-4.1.9).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub typ: Option<String>,
-    /// The algorithm used
-    ///
-    /// Defined in [RFC7515#4.1.1](https://tools.ietf.org/html/rfc7515#section-4.1.1).
-    pub alg: Algorithm,
     /// Content type
     ///
     /// Defined in [RFC7519#5.2](https://tools.ietf.org/html/rfc7519#section-5.2).
@@ -27,43 +35,108 @@ pub struct Header {
     /// Defined in [RFC7515#4.1.2](https://tools.ietf.org/html/rfc7515#section-4.1.2).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub jku: Option<String>,
+    /// JSON Web Key
+    ///
+    /// Defined in [RFC7515#4.1.3](https://tools.ietf.org/html/rfc7515#section-4.1.3).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwk: Option<JWK>,
     /// Key ID
     ///
     /// Defined in [RFC7515#4.1.4](https://tools.ietf.org/html/rfc7515#section-4.1.4).
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        deserialize_with = "crate::serialization::deserialize_kid",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub kid: Option<String>,
     /// X.509 URL
     ///
     /// Defined in [RFC7515#4.1.5](https://tools.ietf.org/html/rfc7515#section-4.1.5).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub x5u: Option<String>,
+    /// X.509 certificate chain
+    ///
+    /// Defined in [RFC7515#4.1.6](https://tools.ietf.org/html/rfc7515#section-4.1.6).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x5c: Option<Vec<String>>,
     /// X.509 certificate thumbprint
     ///
     /// Defined in [RFC7515#4.1.7](https://tools.ietf.org/html/rfc7515#section-4.1.7).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub x5t: Option<String>,
+    /// X.509 certificate SHA-256 thumbprint
+    ///
+    /// Defined in [RFC7515#4.1.8](https://tools.ietf.org/html/rfc7515#section-4.1.8).
+    #[serde(rename = "x5t#S256", skip_serializing_if = "Option::is_none")]
+    pub x5t_s256: Option<String>,
+    /// Critical header parameters that must be understood by the recipient
+    ///
+    /// Defined in [RFC7515#4.1.11](https://tools.ietf.org/html/rfc7515#section-4.1.11).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crit: Option<Vec<String>>,
+    /// Whether the payload is base64url encoded, `false` means it is included as is and has to be
+    /// listed in `crit`
+    ///
+    /// Defined in [RFC7797#3](https://tools.ietf.org/html/rfc7797#section-3).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub b64: Option<bool>,
+    /// Any other header parameters, keyed by name
+    #[serde(flatten)]
+    pub extras: BTreeMap<String, Value>,
 }
 
 impl Header {
     /// Returns a JWT header with the algorithm given
     pub fn new(algorithm: Algorithm) -> Self {
         Header {
-            typ: Some("JWT".to_string()),
             alg: algorithm,
+            typ: Some("JWT".to_string()),
             cty: None,
             jku: None,
+            jwk: None,
             kid: None,
             x5u: None,
+            x5c: None,
             x5t: None,
+            x5t_s256: None,
+            crit: None,
+            b64: None,
+            extras: BTreeMap::new(),
         }
     }
 
+    /// Returns a JWT header with the algorithm and key id given
+    pub fn new_with_kid<T: ToString>(algorithm: Algorithm, kid: T) -> Self {
+        Header { kid: Some(kid.to_string()), ..Header::new(algorithm) }
+    }
+
+    /// Sets `x5t#S256` to the SHA-256 thumbprint of the DER encoded certificate `cert_der`
+    pub fn set_x5t_s256(&mut self, cert_der: &[u8]) {
+        self.x5t_s256 = Some(b64_encode(&Sha256::digest(cert_der)));
+    }
+
+    /// Converts the header to a JSON value, for code that edits headers without knowing all
+    /// their fields. Unknown parameters from `extras` are kept at the top level.
+    pub fn to_value(&self) -> Result<Value> {
+        Ok(serde_json::to_value(self)?)
+    }
+
+    /// Converts a JSON value back to a header, parameters this crate doesn't know about end
+    /// up in `extras`
+    pub fn from_value(value: Value) -> Result<Self> {
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Whether the payload is unencoded, as per [RFC7797](https://tools.ietf.org/html/rfc7797)
+    pub(crate) fn is_unencoded_payload(&self) -> bool {
+        self.b64 == Some(false)
+    }
+
     /// Converts an encoded part into the Header struct if possible
     pub(crate) fn from_encoded(encoded_part: &str) -> Result<Self> {
-        let decoded = b64_decode(encoded_part)?;
-        let s = String::from_utf8(decoded)?;
+        let decoded = b64_decode_json(encoded_part)?;
 
-        Ok(serde_json::from_str(&s)?)
+        Ok(serde_json::from_slice(&decoded)?)
     }
 }
 
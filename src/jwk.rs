@@ -1,30 +1,141 @@
 use std::convert::{TryFrom, TryInto};
+use std::fmt;
 // use std::time::Duration;
 
 use crate::{dangerous_insecure_decode_with_validation, decode, decode_header};
-use crate::{errors::new_error, Algorithm, DecodingKey, TokenData, Validation};
+use crate::{errors::new_error, Algorithm, AlgorithmFamily, DecodingKey, TokenData, Validation};
 use serde::{self, de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::errors::{Error, ErrorKind, Result};
+use crate::serialization::b64_encode;
+use sha2::{Digest, Sha256};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct JWK {
     pub kty: JsonWebKeyTypes,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub alg: Option<Algorithm>,
+    #[serde(
+        default,
+        deserialize_with = "crate::serialization::deserialize_kid",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub kid: Option<String>,
-    #[serde(rename = "use")]
+    #[serde(rename = "use", skip_serializing_if = "Option::is_none")]
     pub key_use: Option<JwkPublicKeyUse>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub e: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub n: Option<String>,
+    /// The secret of an `oct` (symmetric) key, base64url encoded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub k: Option<String>,
+    /// The private exponent of an RSA private key, base64url encoded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub d: Option<String>,
+    /// The first prime factor of an RSA private key, base64url encoded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p: Option<String>,
+    /// The second prime factor of an RSA private key, base64url encoded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub q: Option<String>,
+}
+
+/// The private members (`k`, `d`, `p` and `q`) are redacted, so keys can be logged safely
+impl fmt::Debug for JWK {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redacted = |member: &Option<String>| member.as_ref().map(|_| "[redacted]");
+        f.debug_struct("JWK")
+            .field("kty", &self.kty)
+            .field("alg", &self.alg)
+            .field("kid", &self.kid)
+            .field("key_use", &self.key_use)
+            .field("e", &self.e)
+            .field("n", &self.n)
+            .field("k", &redacted(&self.k))
+            .field("d", &redacted(&self.d))
+            .field("p", &redacted(&self.p))
+            .field("q", &redacted(&self.q))
+            .finish()
+    }
 }
 
+impl JWK {
+    /// The [RFC7638](https://tools.ietf.org/html/rfc7638) SHA-256 thumbprint of the key,
+    /// base64url encoded
+    pub fn thumbprint(&self) -> Result<String> {
+        let members = match (self.kty, &self.n, &self.e) {
+            (JsonWebKeyTypes::Rsa, Some(n), Some(e)) => {
+                format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, e, n)
+            }
+            (JsonWebKeyTypes::Rsa, _, _) => return Err(new_error(ErrorKind::InvalidRsaKey)),
+            (JsonWebKeyTypes::OctetSeq, _, _) => match self.k {
+                Some(ref k) => format!(r#"{{"k":"{}","kty":"oct"}}"#, k),
+                None => return Err(new_error(ErrorKind::InvalidHmacSecret)),
+            },
+            (_, _, _) => return Err(new_error(ErrorKind::UnsupportedKeyType)),
+        };
+        Ok(b64_encode(&Sha256::digest(members.as_bytes())))
+    }
+}
+
+/// A JSON Web Key Set.
+///
+/// Entries this crate can't parse, like `OKP` keys or algorithms it doesn't know, don't make
+/// the whole set fail: they are kept aside in [unsupported](#method.unsupported) and written
+/// back when the set is serialized, so the supported keys remain usable.
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "RawJWKS", into = "RawJWKS")]
 pub struct JWKS {
     keys: Vec<JWK>,
+    unsupported: Vec<serde_json::Value>,
+}
+
+/// The JWKS as found in JSON, before sorting out the keys we support
+#[derive(Serialize, Deserialize)]
+struct RawJWKS {
+    keys: Vec<serde_json::Value>,
+}
+
+impl From<RawJWKS> for JWKS {
+    fn from(raw: RawJWKS) -> Self {
+        let mut jwks = JWKS { keys: Vec::new(), unsupported: Vec::new() };
+        for key in raw.keys {
+            match JWK::deserialize(&key) {
+                Ok(jwk) => jwks.keys.push(jwk),
+                Err(_) => jwks.unsupported.push(key),
+            }
+        }
+        jwks
+    }
+}
+
+impl From<JWKS> for RawJWKS {
+    fn from(jwks: JWKS) -> Self {
+        let keys = jwks.keys.iter().filter_map(|key| serde_json::to_value(key).ok());
+        RawJWKS { keys: keys.chain(jwks.unsupported).collect() }
+    }
+}
+
+impl JWKS {
+    /// The keys that could be parsed
+    pub fn keys(&self) -> &[JWK] {
+        &self.keys
+    }
+
+    /// The entries that couldn't be parsed as a key this crate knows, as found in the JSON
+    pub fn unsupported(&self) -> &[serde_json::Value] {
+        &self.unsupported
+    }
+
+    /// Find a key by key id (KID)
+    pub fn find(&self, kid: &str) -> Option<&JWK> {
+        self.keys.iter().find(|key| key.kid.as_deref() == Some(kid))
+    }
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum JsonWebKeyTypes {
     #[serde(rename = "RSA")]
     Rsa,
@@ -33,7 +144,7 @@ pub enum JsonWebKeyTypes {
     #[serde(rename = "oct")]
     OctetSeq,
 }
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum JwkPublicKeyUse {
     #[serde(rename = "sig")]
     Signature,
@@ -70,7 +181,7 @@ impl JWKDecodingKey {
 impl TryFrom<JWK> for JWKDecodingKey {
     type Error = Error;
 
-    fn try_from(JWK { kid, alg, kty, key_use: _, n, e }: JWK) -> Result<JWKDecodingKey> {
+    fn try_from(JWK { kid, alg, kty, n, e, .. }: JWK) -> Result<JWKDecodingKey> {
         let key = match (kty, n, e) {
             (JsonWebKeyTypes::Rsa, Some(n), Some(e)) => {
                 JWKDecodingKey::new(kid, alg, DecodingKey::from_rsa_components(&n, &e)?)
@@ -85,6 +196,7 @@ impl TryFrom<JWK> for JWKDecodingKey {
 #[derive(Clone)]
 pub struct JWKDecodingKeySet {
     pub(crate) keys: Vec<JWKDecodingKey>,
+    pub(crate) require_kid: bool,
 }
 
 impl TryFrom<JWKS> for JWKDecodingKeySet {
@@ -104,7 +216,7 @@ impl TryFrom<JWKS> for JWKDecodingKeySet {
 #[allow(dead_code)]
 impl JWKDecodingKeySet {
     pub fn new() -> JWKDecodingKeySet {
-        JWKDecodingKeySet { keys: Vec::new() }
+        JWKDecodingKeySet { keys: Vec::new(), require_kid: false }
     }
 
     pub fn clear_keys(&mut self) {
@@ -121,6 +233,12 @@ impl JWKDecodingKeySet {
         self.keys.len()
     }
 
+    /// Reject tokens without a `kid` with `ErrorKind::InvalidToken` in [verify](#method.verify),
+    /// instead of trying every key
+    pub fn set_require_kid(&mut self, require: bool) {
+        self.require_kid = require;
+    }
+
     /// Manually add a key to the keystore
     pub fn add_key(&mut self, key: JWKDecodingKey) {
         self.keys.push(key);
@@ -131,7 +249,8 @@ impl JWKDecodingKeySet {
     ///
     /// A token is considered valid if:
     /// * Is well formed
-    /// * Has a `kid` field that matches a public signature `kid
+    /// * Has a `kid` field that matches a public signature `kid`, if it has one. Tokens without
+    ///   one are tried with every key, unless [set_require_kid](#method.set_require_kid) is set
     /// * Signature matches public key
     /// * It is not expired
     /// * The `nbf` is not set to before now
@@ -151,6 +270,8 @@ impl JWKDecodingKeySet {
         // } else {true}}).find_map(|key| {Some(decode::<serde_json::Value>(token, &key.key, &validation).unwrap())}));
         let data = if let Some(ref kid) = header.kid {
             self.keys_by_id(kid.clone())
+        } else if self.require_kid {
+            return Err(new_error(ErrorKind::InvalidToken));
         } else {
             self.keys.clone()
         }
@@ -169,15 +290,147 @@ impl Default for JWKDecodingKeySet {
     }
 }
 
+/// Retrieves the JWKS published at a `jku` URL, using the HTTP client of your choice.
+#[cfg(feature = "jku")]
+pub trait JwksFetcher {
+    /// Fetch and parse the JWKS at `url`, which has already been checked against the allowlist.
+    ///
+    /// Any error returned here is passed through unchanged by [decode_with_jku]: use
+    /// `ErrorKind::Json` when the body isn't a valid JWKS and `ErrorKind::UntrustedKey` when the
+    /// request could not be completed. Implementations must not follow redirects to another URL,
+    /// or at least must check the final URL is also one of the allowed ones: a redirect would
+    /// otherwise let the allowlisted host hand out keys from anywhere.
+    fn fetch(&self, url: &str) -> Result<JWKS>;
+}
+
+/// Verify a JWT token against the JWKS its `jku` header points to.
+///
+/// The `jku` must be exactly equal to one of `allowed_urls`, otherwise `ErrorKind::UntrustedKey` is
+/// returned without fetching anything: the header is attacker controlled, so trusting any URL
+/// would let anyone sign tokens with their own keys (and make us send requests on their behalf).
+///
+/// Requires the `jku` feature.
+#[cfg(feature = "jku")]
+pub fn decode_with_jku<T: DeserializeOwned>(
+    token: &str,
+    allowed_urls: &[&str],
+    fetcher: &impl JwksFetcher,
+    validation: &Validation,
+) -> Result<TokenData<T>> {
+    let header = decode_header(token)?;
+    let jku = header.jku.ok_or_else(|| new_error(ErrorKind::InvalidToken))?;
+    if !allowed_urls.contains(&jku.as_str()) {
+        return Err(new_error(ErrorKind::UntrustedKey));
+    }
+
+    let key_set: JWKDecodingKeySet = fetcher.fetch(&jku)?.try_into()?;
+    key_set.verify(token, validation)
+}
+
+/// Verify a JWT token with the RSA key of `jwks` whose `kid` matches the one in its header,
+/// the usual setup with a cloud identity provider.
+///
+/// Tokens without a `kid` are rejected with `ErrorKind::InvalidToken`, and `ErrorKind::NoWorkingKey`
+/// is returned if no RSA key has that `kid`.
+/// Keys without an `alg`, as published by Azure AD, use the header algorithm as long as
+/// `validation` allows it.
+pub fn decode_with_jwks<T: DeserializeOwned>(
+    token: &str,
+    jwks: &JWKS,
+    validation: &Validation,
+) -> Result<TokenData<T>> {
+    decode_with_jwks_returning_key(token, jwks, validation).map(|(token_data, _)| token_data)
+}
+
+/// Like [decode_with_jwks], but also returns the key of `jwks` that verified the token, for
+/// logging or metrics.
+pub fn decode_with_jwks_returning_key<'a, T: DeserializeOwned>(
+    token: &str,
+    jwks: &'a JWKS,
+    validation: &Validation,
+) -> Result<(TokenData<T>, &'a JWK)> {
+    let header = decode_header(token)?;
+    let kid = header.kid.ok_or_else(|| new_error(ErrorKind::InvalidToken))?;
+    let jwk = match jwks.find(&kid) {
+        Some(jwk) if jwk.kty == JsonWebKeyTypes::Rsa => jwk,
+        _ => return Err(new_error(ErrorKind::NoWorkingKey)),
+    };
+
+    let key: JWKDecodingKey = jwk.clone().try_into()?;
+    if let Some(alg) = key.alg {
+        if alg != header.alg {
+            return Err(new_error(ErrorKind::InvalidAlgorithm));
+        }
+    }
+    Ok((decode(token, &key.key, validation)?, jwk))
+}
+
+/// Verify a JWT token with a pinned JWK, using the algorithm declared by the key rather than
+/// trusting the one in the header.
+///
+/// The key must have an `alg` matching its `kty`, as the key type alone doesn't say which
+/// algorithm to use (RSA keys work with both RS* and PS*). The header `alg` is only checked to
+/// be the same, any disagreement is rejected with `ErrorKind::InvalidAlgorithm`.
+///
+/// Symmetric (`oct`) keys aren't supported and are rejected with `ErrorKind::UnsupportedKeyType`
+/// before looking at the token.
+pub fn decode_with_jwk_alg<T: DeserializeOwned>(
+    token: &str,
+    jwk: &JWK,
+    validation: &Validation,
+) -> Result<TokenData<T>> {
+    let alg = jwk.alg.ok_or_else(|| new_error(ErrorKind::InvalidAlgorithm))?;
+    let family = match jwk.kty {
+        JsonWebKeyTypes::Rsa => AlgorithmFamily::Rsa,
+        JsonWebKeyTypes::Ec => AlgorithmFamily::Ec,
+        JsonWebKeyTypes::OctetSeq => return Err(new_error(ErrorKind::UnsupportedKeyType)),
+    };
+    if alg.family() != family || decode_header(token)?.alg != alg {
+        return Err(new_error(ErrorKind::InvalidAlgorithm));
+    }
+
+    let key: JWKDecodingKey = jwk.clone().try_into()?;
+    decode(token, &key.key, validation)
+}
+
+/// Verify a JWT token using the key embedded in its `jwk` header.
+///
+/// The key's thumbprint (see [JWK::thumbprint]) must be one of `allowed_thumbprints`, otherwise
+/// `ErrorKind::UntrustedKey` is returned: anyone can embed their own key in a token, so it
+/// only proves something if we already know which keys to trust.
+pub fn decode_with_embedded_jwk<T: DeserializeOwned>(
+    token: &str,
+    allowed_thumbprints: &[&str],
+    validation: &Validation,
+) -> Result<TokenData<T>> {
+    let header = decode_header(token)?;
+    let jwk = header.jwk.ok_or_else(|| new_error(ErrorKind::InvalidToken))?;
+    if !allowed_thumbprints.contains(&jwk.thumbprint()?.as_str()) {
+        return Err(new_error(ErrorKind::UntrustedKey));
+    }
+
+    let key: JWKDecodingKey = jwk.try_into()?;
+    if let Some(alg) = key.alg {
+        if alg != header.alg {
+            return Err(new_error(ErrorKind::InvalidAlgorithm));
+        }
+    }
+    decode(token, &key.key, validation)
+}
+
 #[cfg(test)]
 mod tests {
     use std::time::{SystemTime, UNIX_EPOCH};
 
     use rsa::pkcs8::DecodePrivateKey;
-    use serde::{Deserialize, Serialize};
+    use serde::Deserialize;
 
     use crate::{
-        jwk::{JWKDecodingKeySet, JWKS},
+        errors::ErrorKind,
+        jwk::{
+            decode_with_embedded_jwk, decode_with_jwk_alg, decode_with_jwks,
+            decode_with_jwks_returning_key, JWKDecodingKeySet, JWK, JWKS,
+        },
         Algorithm,
     };
 
@@ -211,21 +464,26 @@ Ko5K8hGLY0C471Wy9yWk+hAI
 -----END PRIVATE KEY-----
 ";
 
-    fn valid_token() -> String {
+    fn now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    /// `TEST_CLAIMS` with an `exp` `seconds` from now
+    fn claims_expiring_in(seconds: u64) -> serde_json::Map<String, serde_json::Value> {
         let mut claims: serde_json::Map<String, serde_json::Value> =
             serde_json::from_str(TEST_CLAIMS).unwrap();
-        claims["exp"] =
-            (SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() + 10).into();
+        claims["exp"] = (now() + seconds).into();
+        claims
+    }
+
+    fn valid_token() -> String {
+        let claims = claims_expiring_in(10);
 
         encode_token(claims)
     }
     fn early_token() -> String {
-        let mut claims: serde_json::Map<String, serde_json::Value> =
-            serde_json::from_str(TEST_CLAIMS).unwrap();
-        claims["nbf"] =
-            (SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() + 100).into();
-        claims["exp"] =
-            (SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() + 200).into();
+        let mut claims = claims_expiring_in(200);
+        claims["nbf"] = (now() + 100).into();
 
         encode_token(claims)
     }
@@ -236,12 +494,18 @@ Ko5K8hGLY0C471Wy9yWk+hAI
         encode_token(claims)
     }
     fn encode_token(claims: serde_json::Map<String, serde_json::Value>) -> String {
+        let mut header = crate::Header::new(crate::Algorithm::RS256);
+        header.kid = Some("1".to_owned());
+        encode_token_with_header(&header, claims)
+    }
+    fn encode_token_with_header(
+        header: &crate::Header,
+        claims: serde_json::Map<String, serde_json::Value>,
+    ) -> String {
         let key =
             crate::EncodingKey::from_rsa(rsa::RsaPrivateKey::from_pkcs8_pem(PRIVATE_KEY).unwrap())
                 .unwrap();
-        let mut header = crate::Header::new(crate::Algorithm::RS256);
-        header.kid = Some("1".to_owned());
-        crate::encode(&header, &claims, &key).unwrap()
+        crate::encode(header, &claims, &key).unwrap()
     }
 
     pub const TEST_CLAIMS: &str = r#"
@@ -257,19 +521,10 @@ Ko5K8hGLY0C471Wy9yWk+hAI
         "nbf": 300,
         "email": "alovelace@example.com"
     }"#;
-    pub const KEY_URL: &str =
-        "https://raw.githubusercontent.com/jfbilodeau/jwks-client/0.1.8/test/test-jwks.json";
     pub const E: &str = "AQAB";
     pub const N: &str = "t5N44H1mpb5Wlx_0e7CdoKTY8xt-3yMby8BgNdagVNkeCkZ4pRbmQXRWNC7qn__Zaxx9dnzHbzGCul5W0RLfd3oB3PESwsrQh-oiXVEPTYhvUPQkX0vBfCXJtg_zY2mY1DxKOIiXnZ8PaK_7Sx0aMmvR__0Yy2a5dIAWCmjPsxn-PcGZOkVUm-D5bH1-ZStcA_68r4ZSPix7Szhgl1RoHb9Q6JSekyZqM0Qfwhgb7srZVXC_9_m5PEx9wMVNYpYJBrXhD5IQm9RzE9oJS8T-Ai-4_5mNTNXI8f1rrYgffWS4wf9cvsEihrvEg9867B2f98L7ux9Llle7jsHCtwgV1w";
     pub const INV_CERT: &str = ".XXXeTQnwXrri_uY55fS4IygseBzzbosDM1hP153EZXzNlLH5s29kdlGt2mL_KIjYmQa8hmptt9RwKJHBtw6l4KFHvIcuif86Ix-iI2fCpqNnKyGZfgERV51NXk1THkgWj0GQB6X5cvOoFIdHa9XvgPl_rVmzXSUYDgkhd2t01FOjQeeT6OL2d9KdlQHJqAsvvKVc3wnaYYoSqv2z0IluvK93Tk1dUBU2yWXH34nX3GAVGvIoFoNRiiFfZwFlnz78G0b2fQV7B5g5F8XlNRdD1xmVZXU8X2-xh9LqRpnEakdhecciFHg0u6AyC4c00rlo_HBb69wlXajQ3R4y26Kpxn7HA";
 
-    #[derive(Debug, Serialize, Deserialize)]
-    pub struct TestPayload {
-        pub iss: String,
-        pub name: String,
-        pub email: String,
-    }
-
     #[derive(Deserialize, Debug)]
     struct TestClaims {
         iss: String,
@@ -280,7 +535,7 @@ Ko5K8hGLY0C471Wy9yWk+hAI
     #[test]
     fn test_from_json() {
         use std::convert::TryInto;
-        let jwks: JWKS = reqwest::blocking::get(KEY_URL).unwrap().json().unwrap();
+        let jwks: JWKS = serde_json::from_str(include_str!("../tests/jwk/test-jwks.json")).unwrap();
         assert_eq!(jwks.keys.len(), 2);
         let key_set: JWKDecodingKeySet = jwks.try_into().unwrap();
         assert_eq!(key_set.keys.len(), 1);
@@ -343,6 +598,7 @@ Ko5K8hGLY0C471Wy9yWk+hAI
             sub: None,
             aud: None,
             iss: Some("https://example.com/test".to_owned()),
+            ..crate::Validation::default()
         };
 
         let result: Result<crate::TokenData<TestClaims>, _> =
@@ -385,6 +641,7 @@ Ko5K8hGLY0C471Wy9yWk+hAI
             sub: None,
             aud: None,
             iss: Some("https://example.com/test".to_owned()),
+            ..crate::Validation::default()
         };
 
         let _result: crate::TokenData<()> = key_set.verify(&valid_token(), &validation).unwrap();
@@ -408,6 +665,7 @@ Ko5K8hGLY0C471Wy9yWk+hAI
             sub: None,
             aud: None,
             iss: Some("https://example.com/test".to_owned()),
+            ..crate::Validation::default()
         };
         let valid_token = valid_token();
         let mut split = valid_token.rsplitn(2, '.');
@@ -416,4 +674,470 @@ Ko5K8hGLY0C471Wy9yWk+hAI
         println!("{:?}", token);
         let _result: crate::TokenData<()> = key_set.verify(&token, &validation).unwrap();
     }
+
+    #[cfg(feature = "jku")]
+    mod jku {
+        use super::*;
+        use crate::{
+            errors::{new_error, ErrorKind},
+            jwk::{decode_with_jku, JwksFetcher},
+        };
+
+        const JKU: &str = "https://example.com/.well-known/jwks.json";
+
+        struct MockFetcher;
+
+        impl JwksFetcher for MockFetcher {
+            fn fetch(&self, url: &str) -> crate::errors::Result<JWKS> {
+                assert_eq!(url, JKU, "only allowed URLs should be fetched");
+                Ok(serde_json::from_str(include_str!("../tests/jwk/test-jwks.json")).unwrap())
+            }
+        }
+
+        struct FailingFetcher;
+
+        impl JwksFetcher for FailingFetcher {
+            fn fetch(&self, _url: &str) -> crate::errors::Result<JWKS> {
+                Err(new_error(ErrorKind::UntrustedKey))
+            }
+        }
+
+        fn jku_token(jku: &str, kid: &str) -> String {
+            let claims = claims_expiring_in(10);
+            let mut header = crate::Header::new(crate::Algorithm::RS256);
+            header.kid = Some(kid.to_owned());
+            header.jku = Some(jku.to_owned());
+            encode_token_with_header(&header, claims)
+        }
+
+        #[test]
+        fn test_decode_with_jku() {
+            let validation = crate::Validation::new(Algorithm::RS256);
+            let result: Result<crate::TokenData<TestClaims>, _> =
+                decode_with_jku(&jku_token(JKU, "1"), &[JKU], &MockFetcher, &validation);
+
+            assert!(result.is_ok(), "{:?}", result);
+            assert_eq!("Ada Lovelace", result.unwrap().claims.name);
+        }
+
+        #[test]
+        fn test_decode_with_jku_not_allowed() {
+            let validation = crate::Validation::new(Algorithm::RS256);
+            let result: Result<crate::TokenData<TestClaims>, _> = decode_with_jku(
+                &jku_token("https://evil.example.com/jwks.json", "1"),
+                &[JKU],
+                &MockFetcher,
+                &validation,
+            );
+
+            match result.unwrap_err().kind() {
+                ErrorKind::UntrustedKey => (),
+                t => panic!("{:?}", t),
+            };
+        }
+
+        #[test]
+        fn test_decode_with_jku_fetch_error() {
+            let validation = crate::Validation::new(Algorithm::RS256);
+            let result: Result<crate::TokenData<TestClaims>, _> =
+                decode_with_jku(&jku_token(JKU, "1"), &[JKU], &FailingFetcher, &validation);
+
+            match result.unwrap_err().kind() {
+                ErrorKind::UntrustedKey => (),
+                t => panic!("{:?}", t),
+            };
+        }
+
+        #[test]
+        fn test_decode_with_jku_unknown_kid() {
+            let validation = crate::Validation::new(Algorithm::RS256);
+            let result: Result<crate::TokenData<TestClaims>, _> =
+                decode_with_jku(&jku_token(JKU, "2"), &[JKU], &MockFetcher, &validation);
+
+            match result.unwrap_err().kind() {
+                ErrorKind::NoWorkingKey => (),
+                t => panic!("{:?}", t),
+            };
+        }
+    }
+
+    // Shaped like the JWKS published by cloud identity providers, with a rotated out key
+    const IDP_JWKS: &str = r#"{
+        "keys": [
+            {
+                "kty": "RSA",
+                "use": "sig",
+                "alg": "RS256",
+                "kid": "6f7254101f56e41cf35c9926de84a2d552b4c6f1",
+                "n": "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+                "e": "AQAB"
+            },
+            {
+                "kty": "RSA",
+                "use": "sig",
+                "alg": "RS256",
+                "kid": "a3b762f871cdb3bae0044c649622fc1396eda3e3",
+                "n": "t5N44H1mpb5Wlx_0e7CdoKTY8xt-3yMby8BgNdagVNkeCkZ4pRbmQXRWNC7qn__Zaxx9dnzHbzGCul5W0RLfd3oB3PESwsrQh-oiXVEPTYhvUPQkX0vBfCXJtg_zY2mY1DxKOIiXnZ8PaK_7Sx0aMmvR__0Yy2a5dIAWCmjPsxn-PcGZOkVUm-D5bH1-ZStcA_68r4ZSPix7Szhgl1RoHb9Q6JSekyZqM0Qfwhgb7srZVXC_9_m5PEx9wMVNYpYJBrXhD5IQm9RzE9oJS8T-Ai-4_5mNTNXI8f1rrYgffWS4wf9cvsEihrvEg9867B2f98L7ux9Llle7jsHCtwgV1w",
+                "e": "AQAB"
+            }
+        ]
+    }"#;
+
+    fn kid_token(kid: &str) -> String {
+        let claims = claims_expiring_in(10);
+        encode_token_with_header(&crate::Header::new_with_kid(Algorithm::RS256, kid), claims)
+    }
+
+    #[test]
+    fn test_decode_with_jwks() {
+        let jwks: JWKS = serde_json::from_str(IDP_JWKS).unwrap();
+        let validation = crate::Validation::new(Algorithm::RS256);
+
+        let result: Result<crate::TokenData<TestClaims>, _> = decode_with_jwks(
+            &kid_token("a3b762f871cdb3bae0044c649622fc1396eda3e3"),
+            &jwks,
+            &validation,
+        );
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!("Ada Lovelace", result.unwrap().claims.name);
+
+        // Signed with our key but claiming to be the other one
+        let result: Result<crate::TokenData<TestClaims>, _> = decode_with_jwks(
+            &kid_token("6f7254101f56e41cf35c9926de84a2d552b4c6f1"),
+            &jwks,
+            &validation,
+        );
+        match result.unwrap_err().kind() {
+            ErrorKind::InvalidSignature => (),
+            t => panic!("{:?}", t),
+        };
+
+        let result: Result<crate::TokenData<TestClaims>, _> =
+            decode_with_jwks(&kid_token("unknown"), &jwks, &validation);
+        match result.unwrap_err().kind() {
+            ErrorKind::NoWorkingKey => (),
+            t => panic!("{:?}", t),
+        };
+    }
+
+    #[test]
+    fn test_decode_with_jwks_returning_key() {
+        let jwks: JWKS = serde_json::from_str(IDP_JWKS).unwrap();
+        let token = kid_token("a3b762f871cdb3bae0044c649622fc1396eda3e3");
+
+        let (token_data, jwk) = decode_with_jwks_returning_key::<TestClaims>(
+            &token,
+            &jwks,
+            &crate::Validation::new(Algorithm::RS256),
+        )
+        .unwrap();
+        assert_eq!(jwk.kid, token_data.header.kid);
+        assert_eq!(jwk.kid, crate::decode_header(&token).unwrap().kid);
+    }
+
+    #[test]
+    fn test_oct_jwk_decoding_key() {
+        let jwk: JWK = serde_json::from_value(serde_json::json!({
+            "kty": "oct",
+            "alg": "HS256",
+            "kid": "internal",
+            "k": "c2VjcmV0"
+        }))
+        .unwrap();
+        let exp = now() + 10;
+        let token = crate::encode(
+            &crate::Header::new_with_kid(Algorithm::HS256, "internal"),
+            &serde_json::json!({ "sub": "b@b.com", "exp": exp }),
+            &crate::EncodingKey::from_hmac_secret(b"secret"),
+        )
+        .unwrap();
+
+        match crate::DecodingKey::from_jwk(&jwk, false).unwrap_err().kind() {
+            ErrorKind::UntrustedKey => (),
+            t => panic!("{:?}", t),
+        };
+
+        let key = crate::DecodingKey::from_jwk(&jwk, true).unwrap();
+        assert_eq!(key, crate::DecodingKey::from_hmac_secret(b"secret"));
+        let result: Result<crate::TokenData<serde_json::Value>, _> =
+            crate::decode(&token, &key, &crate::Validation::new(Algorithm::HS256));
+        assert!(result.is_ok(), "{:?}", result);
+
+        // The symmetric opt-in doesn't change anything for RSA keys
+        assert!(crate::DecodingKey::from_jwk(&pinned_jwk(None), false).is_ok());
+    }
+
+    #[test]
+    fn test_decode_with_jwks_numeric_kid() {
+        let jwk = JWK { kid: Some("3".to_string()), ..pinned_jwk(Some(Algorithm::RS256)) };
+        let jwks: JWKS = serde_json::from_value(serde_json::json!({ "keys": [jwk] })).unwrap();
+
+        let claims = claims_expiring_in(10);
+        let message = format!(
+            "{}.{}",
+            crate::serialization::b64_encode(br#"{"alg":"RS256","kid":3}"#),
+            crate::serialization::b64_encode(&serde_json::to_vec(&claims).unwrap())
+        );
+        let key =
+            crate::EncodingKey::from_rsa(rsa::RsaPrivateKey::from_pkcs8_pem(PRIVATE_KEY).unwrap())
+                .unwrap();
+        let signature = crate::crypto::sign(&message, &key, Algorithm::RS256).unwrap();
+        let token = format!("{}.{}", message, signature);
+
+        assert_eq!(crate::decode_header(&token).unwrap().kid.as_deref(), Some("3"));
+        let result: Result<crate::TokenData<TestClaims>, _> =
+            decode_with_jwks(&token, &jwks, &crate::Validation::new(Algorithm::RS256));
+        assert!(result.is_ok(), "{:?}", result);
+
+        // Numeric kids in the JWKS work too
+        let jwks: JWKS =
+            serde_json::from_str(&serde_json::to_string(&jwks).unwrap().replace(r#""3""#, "3"))
+                .unwrap();
+        assert!(jwks.find("3").is_some());
+    }
+
+    #[test]
+    fn test_key_set_require_kid() {
+        let jwks: JWKS = serde_json::from_str(IDP_JWKS).unwrap();
+        let mut key_set: JWKDecodingKeySet = jwks.try_into().unwrap();
+        let claims = claims_expiring_in(10);
+        let token = encode_token_with_header(&crate::Header::new(Algorithm::RS256), claims);
+        let validation = crate::Validation::new(Algorithm::RS256);
+
+        let result: Result<crate::TokenData<TestClaims>, _> = key_set.verify(&token, &validation);
+        assert!(result.is_ok(), "{:?}", result);
+
+        key_set.set_require_kid(true);
+        let result: Result<crate::TokenData<TestClaims>, _> = key_set.verify(&token, &validation);
+        match result.unwrap_err().kind() {
+            ErrorKind::InvalidToken => (),
+            t => panic!("{:?}", t),
+        };
+
+        let result: Result<crate::TokenData<TestClaims>, _> =
+            key_set.verify(&kid_token("a3b762f871cdb3bae0044c649622fc1396eda3e3"), &validation);
+        assert!(result.is_ok(), "{:?}", result);
+    }
+
+    #[test]
+    fn test_jwks_with_unsupported_key() {
+        let mut raw: serde_json::Value = serde_json::from_str(IDP_JWKS).unwrap();
+        let okp = serde_json::json!({
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "alg": "EdDSA",
+            "kid": "ed",
+            "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"
+        });
+        raw["keys"].as_array_mut().unwrap().insert(0, okp.clone());
+
+        let jwks: JWKS = serde_json::from_value(raw).unwrap();
+        assert_eq!(jwks.keys().len(), 2);
+        assert_eq!(jwks.unsupported().len(), 1);
+        assert_eq!(jwks.unsupported()[0], okp);
+        assert!(jwks.find("ed").is_none());
+
+        let result: Result<crate::TokenData<TestClaims>, _> = decode_with_jwks(
+            &kid_token("a3b762f871cdb3bae0044c649622fc1396eda3e3"),
+            &jwks,
+            &crate::Validation::new(Algorithm::RS256),
+        );
+        assert!(result.is_ok(), "{:?}", result);
+
+        // Nothing is lost when writing the set back
+        let written = serde_json::to_value(&jwks).unwrap();
+        assert_eq!(written["keys"].as_array().unwrap().len(), 3);
+        assert!(written["keys"].as_array().unwrap().contains(&okp));
+    }
+
+    // Azure AD doesn't set `alg` on its keys, the algorithm comes from the token header
+    const AZURE_JWKS: &str = r#"{
+        "keys": [
+            {
+                "kty": "RSA",
+                "use": "sig",
+                "kid": "nOo3ZDrODXEK1jKWhXslHR_KXEg",
+                "x5t": "nOo3ZDrODXEK1jKWhXslHR_KXEg",
+                "n": "t5N44H1mpb5Wlx_0e7CdoKTY8xt-3yMby8BgNdagVNkeCkZ4pRbmQXRWNC7qn__Zaxx9dnzHbzGCul5W0RLfd3oB3PESwsrQh-oiXVEPTYhvUPQkX0vBfCXJtg_zY2mY1DxKOIiXnZ8PaK_7Sx0aMmvR__0Yy2a5dIAWCmjPsxn-PcGZOkVUm-D5bH1-ZStcA_68r4ZSPix7Szhgl1RoHb9Q6JSekyZqM0Qfwhgb7srZVXC_9_m5PEx9wMVNYpYJBrXhD5IQm9RzE9oJS8T-Ai-4_5mNTNXI8f1rrYgffWS4wf9cvsEihrvEg9867B2f98L7ux9Llle7jsHCtwgV1w",
+                "e": "AQAB",
+                "x5c": ["MIIDBTCCAe2gAwIBAgIQN33ROaIJ6bJBWDCxtmJEbjANBgkqhkiG9w0BAQsFADAtMSswKQYDVQQD"]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_decode_with_jwks_without_alg() {
+        let jwks: JWKS = serde_json::from_str(AZURE_JWKS).unwrap();
+        let token = kid_token("nOo3ZDrODXEK1jKWhXslHR_KXEg");
+
+        let result: Result<crate::TokenData<TestClaims>, _> =
+            decode_with_jwks(&token, &jwks, &crate::Validation::new(Algorithm::RS256));
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!("Ada Lovelace", result.unwrap().claims.name);
+
+        let key_set: JWKDecodingKeySet = jwks.clone().try_into().unwrap();
+        let result: Result<crate::TokenData<TestClaims>, _> =
+            key_set.verify(&token, &crate::Validation::new(Algorithm::RS256));
+        assert!(result.is_ok(), "{:?}", result);
+
+        // The header algorithm still has to be an allowed one
+        let result: Result<crate::TokenData<TestClaims>, _> =
+            decode_with_jwks(&token, &jwks, &crate::Validation::new(Algorithm::PS256));
+        match result.unwrap_err().kind() {
+            ErrorKind::InvalidAlgorithm => (),
+            t => panic!("{:?}", t),
+        };
+    }
+
+    fn pinned_jwk(alg: Option<Algorithm>) -> JWK {
+        JWK {
+            kty: crate::jwk::JsonWebKeyTypes::Rsa,
+            alg,
+            kid: Some("1".to_string()),
+            key_use: None,
+            e: Some(E.to_string()),
+            n: Some(N.to_string()),
+            k: None,
+            d: None,
+            p: None,
+            q: None,
+        }
+    }
+
+    #[test]
+    fn test_decode_with_jwk_alg_oct_key() {
+        let jwk: JWK =
+            serde_json::from_value(serde_json::json!({ "kty": "oct", "alg": "HS256" })).unwrap();
+
+        let claims: serde_json::Value = serde_json::from_str(TEST_CLAIMS).unwrap();
+        let token = crate::encode(
+            &crate::Header::new(Algorithm::HS256),
+            &claims,
+            &crate::EncodingKey::from_hmac_secret(b"secret"),
+        )
+        .unwrap();
+
+        let result: Result<crate::TokenData<TestClaims>, _> =
+            decode_with_jwk_alg(&token, &jwk, &crate::Validation::new(Algorithm::HS256));
+        match result.unwrap_err().kind() {
+            ErrorKind::UnsupportedKeyType => (),
+            t => panic!("{:?}", t),
+        };
+    }
+
+    fn token_with_alg(alg: Algorithm) -> String {
+        let claims = claims_expiring_in(10);
+        encode_token_with_header(&crate::Header::new(alg), claims)
+    }
+
+    #[test]
+    fn test_decode_with_jwk_alg() {
+        let validation = crate::Validation {
+            algorithms: vec![Algorithm::RS256, Algorithm::PS256],
+            ..crate::Validation::default()
+        };
+
+        let result: Result<crate::TokenData<TestClaims>, _> = decode_with_jwk_alg(
+            &token_with_alg(Algorithm::RS256),
+            &pinned_jwk(Some(Algorithm::RS256)),
+            &validation,
+        );
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!("Ada Lovelace", result.unwrap().claims.name);
+    }
+
+    #[test]
+    fn test_decode_with_jwk_alg_header_disagrees() {
+        let validation = crate::Validation {
+            algorithms: vec![Algorithm::RS256, Algorithm::PS256],
+            ..crate::Validation::default()
+        };
+
+        // A validly signed token, but with an algorithm the key isn't meant for
+        for jwk in [
+            pinned_jwk(Some(Algorithm::RS256)),
+            pinned_jwk(Some(Algorithm::HS256)),
+            pinned_jwk(None),
+        ] {
+            let result: Result<crate::TokenData<TestClaims>, _> =
+                decode_with_jwk_alg(&token_with_alg(Algorithm::PS256), &jwk, &validation);
+            match result.unwrap_err().kind() {
+                ErrorKind::InvalidAlgorithm => (),
+                t => panic!("{:?}", t),
+            };
+        }
+    }
+
+    // https://tools.ietf.org/html/rfc7638#section-3.1
+    #[test]
+    fn test_thumbprint() {
+        let jwk: JWK = serde_json::from_str(r#"{
+            "kty": "RSA",
+            "n": "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+            "e": "AQAB",
+            "alg": "RS256",
+            "kid": "2011-04-29"
+        }"#)
+        .unwrap();
+        assert_eq!(jwk.thumbprint().unwrap(), "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs");
+    }
+
+    #[test]
+    fn test_debug_redacts_private_members() {
+        for jwk in [
+            serde_json::json!({ "kty": "oct", "k": "c2VjcmV0LWhtYWMta2V5" }),
+            serde_json::json!({
+                "kty": "RSA", "n": N, "e": E, "d": "cHJpdmF0ZS1leHBvbmVudA",
+                "p": "Zmlyc3QtcHJpbWU", "q": "c2Vjb25kLXByaW1l"
+            }),
+        ] {
+            let jwk: JWK = serde_json::from_value(jwk).unwrap();
+            let debug = format!("{:?}", jwk);
+            for secret in [
+                "c2VjcmV0LWhtYWMta2V5",
+                "cHJpdmF0ZS1leHBvbmVudA",
+                "Zmlyc3QtcHJpbWU",
+                "c2Vjb25kLXByaW1l",
+            ] {
+                assert!(!debug.contains(secret), "{}", debug);
+            }
+            assert!(debug.contains("[redacted]"), "{}", debug);
+        }
+    }
+
+    fn embedded_jwk_token() -> (String, String) {
+        let claims = claims_expiring_in(10);
+        let jwk: JWK =
+            serde_json::from_value(serde_json::json!({ "kty": "RSA", "n": N, "e": E })).unwrap();
+        let thumbprint = jwk.thumbprint().unwrap();
+        let mut header = crate::Header::new(crate::Algorithm::RS256);
+        header.jwk = Some(jwk);
+        (encode_token_with_header(&header, claims), thumbprint)
+    }
+
+    #[test]
+    fn test_decode_with_embedded_jwk() {
+        let (token, thumbprint) = embedded_jwk_token();
+        let validation = crate::Validation::new(Algorithm::RS256);
+        let result: Result<crate::TokenData<TestClaims>, _> =
+            decode_with_embedded_jwk(&token, &[&thumbprint], &validation);
+
+        assert!(result.is_ok(), "{:?}", result);
+    }
+
+    #[test]
+    fn test_decode_with_embedded_jwk_untrusted() {
+        let (token, _) = embedded_jwk_token();
+        let validation = crate::Validation::new(Algorithm::RS256);
+        let result: Result<crate::TokenData<TestClaims>, _> = decode_with_embedded_jwk(
+            &token,
+            &["NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"],
+            &validation,
+        );
+
+        match result.unwrap_err().kind() {
+            ErrorKind::UntrustedKey => (),
+            t => panic!("{:?}", t),
+        };
+    }
 }
@@ -6,23 +6,35 @@
 mod algorithms;
 /// Lower level functions, if you want to do something other than JWTs
 pub mod crypto;
+/// Signing and verifying with algorithms that aren't built into this crate
+pub mod custom;
 mod decoding;
 mod encoding;
 /// All the errors that can be encountered while encoding/decoding JWTs
 pub mod errors;
 mod header;
 mod serialization;
+/// Random keys for tests and examples, with the `testkeys` feature. Don't use them in production.
+#[cfg(feature = "testkeys")]
+pub mod testkeys;
+mod timestamp;
 mod validation;
 // JWK and JWKS types and functions
 pub mod jwk;
 
-pub use algorithms::Algorithm;
+pub use algorithms::{Algorithm, AlgorithmFamily};
 #[allow(deprecated)]
 pub use decoding::dangerous_unsafe_decode;
 pub use decoding::{
-    dangerous_insecure_decode, dangerous_insecure_decode_with_validation, decode, decode_header,
-    DecodingKey, TokenData,
+    claim_names, dangerous_insecure_decode, dangerous_insecure_decode_with_validation, decode,
+    decode_borrowed, decode_header, decode_header_and_preview, decode_header_ext,
+    decode_payload_bytes, decode_with_raw, token_ranges, try_parse, DecodingKey, TokenData,
 };
-pub use encoding::{encode, EncodingKey};
+pub use encoding::{
+    encode, encode_canonical, encode_raw, encode_strict, encode_with_ttl, encode_with_ttl_at,
+    reissue, EncodingKey, StandardClaims,
+};
+pub use errors::Result;
 pub use header::Header;
-pub use validation::Validation;
+pub use timestamp::Timestamp;
+pub use validation::{ClaimsValidatorFn, CustomValidator, TimeUnit, Validation};
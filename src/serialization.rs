@@ -2,7 +2,7 @@ use base64::Engine;
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
 use serde_json::map::Map;
-use serde_json::{from_str, to_string, Value};
+use serde_json::{from_slice, to_string, Value};
 
 use crate::errors::Result;
 
@@ -14,11 +14,30 @@ pub(crate) fn b64_encode(input: &[u8]) -> String {
     engine.encode(input)
 }
 
+/// Only canonical input is accepted: non-zero trailing bits would let several strings decode to
+/// the same bytes, making signed tokens malleable.
 pub(crate) fn b64_decode(input: &str) -> Result<Vec<u8>> {
     let engine = base64::engine::GeneralPurpose::new(
         &base64::alphabet::URL_SAFE,
         base64::engine::GeneralPurposeConfig::new()
-            .with_decode_padding_mode(base64::engine::DecodePaddingMode::RequireNone),
+            .with_decode_padding_mode(base64::engine::DecodePaddingMode::RequireNone)
+            .with_decode_allow_trailing_bits(false),
+    );
+    Ok(engine.decode(input)?)
+}
+
+/// Like `b64_decode` for a part holding JSON, which has to be UTF-8: invalid bytes are reported
+/// as `ErrorKind::Utf8` before any deserialization, instead of a less obvious JSON error
+pub(crate) fn b64_decode_json(input: &str) -> Result<Vec<u8>> {
+    Ok(String::from_utf8(b64_decode(input)?)?.into_bytes())
+}
+
+/// Like `b64_decode` but accepts input with or without trailing `=` padding
+pub(crate) fn b64_decode_padding_indifferent(input: &str) -> Result<Vec<u8>> {
+    let engine = base64::engine::GeneralPurpose::new(
+        &base64::alphabet::URL_SAFE,
+        base64::engine::GeneralPurposeConfig::new()
+            .with_decode_padding_mode(base64::engine::DecodePaddingMode::Indifferent),
     );
     Ok(engine.decode(input)?)
 }
@@ -29,14 +48,49 @@ pub(crate) fn b64_encode_part<T: Serialize>(input: &T) -> Result<String> {
     Ok(b64_encode(json.as_bytes()))
 }
 
-/// Decodes from base64 and deserializes from JSON to a struct AND a hashmap of Value so we can
-/// run validation on it
-pub(crate) fn from_jwt_part_claims<B: AsRef<str>, T: DeserializeOwned>(
-    encoded: B,
-) -> Result<(T, Map<String, Value>)> {
-    let s = String::from_utf8(b64_decode(encoded.as_ref())?)?;
+/// Serializes to compact JSON with the keys of every object sorted, so the output only depends
+/// on the data and not on the field order of the types or maps it came from
+pub(crate) fn to_canonical_json<T: Serialize>(input: &T) -> Result<Vec<u8>> {
+    fn sort_keys(value: Value) -> Value {
+        match value {
+            Value::Object(map) => {
+                let mut entries: Vec<_> = map.into_iter().collect();
+                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                Value::Object(entries.into_iter().map(|(k, v)| (k, sort_keys(v))).collect())
+            }
+            Value::Array(values) => Value::Array(values.into_iter().map(sort_keys).collect()),
+            other => other,
+        }
+    }
+
+    Ok(serde_json::to_vec(&sort_keys(serde_json::to_value(input)?))?)
+}
+
+/// Deserializes the decoded payload from JSON to a struct AND a Value of the claims object so we
+/// can run validation on it
+///
+/// The JSON is read straight from the decoded bytes so custom deserializers see exactly what
+/// was signed.
+pub(crate) fn from_jwt_payload<T: DeserializeOwned>(decoded: &[u8]) -> Result<(T, Value)> {
+    let claims: T = from_slice(decoded)?;
+    let validation_map: Map<_, _> = from_slice(decoded)?;
+    Ok((claims, Value::Object(validation_map)))
+}
+
+/// Deserializes an optional key id that some issuers wrongly send as a JSON number, which is
+/// turned into its string form so it can be compared with the `kid` of keys.
+pub(crate) fn deserialize_kid<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<String>, D::Error> {
+    use serde::Deserialize;
 
-    let claims: T = from_str(&s)?;
-    let validation_map: Map<_, _> = from_str(&s)?;
-    Ok((claims, validation_map))
+    match Option::<Value>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Value::String(kid)) => Ok(Some(kid)),
+        Some(Value::Number(kid)) => Ok(Some(kid.to_string())),
+        Some(other) => Err(serde::de::Error::invalid_type(
+            serde::de::Unexpected::Other(&other.to_string()),
+            &"a string or a number",
+        )),
+    }
 }
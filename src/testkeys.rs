@@ -0,0 +1,36 @@
+use rand::RngCore;
+
+use crate::decoding::DecodingKey;
+use crate::encoding::EncodingKey;
+use crate::errors::{new_error, ErrorKind, Result};
+
+/// Generate a random RSA key pair of `bits` bits, usable with the RS* and PS* algorithms
+pub fn generate_rsa(bits: usize) -> Result<(EncodingKey, DecodingKey)> {
+    let key = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), bits)
+        .map_err(|_| new_error(ErrorKind::InvalidRsaKey))?;
+    let public = key.to_public_key();
+    Ok((EncodingKey::from_rsa(key)?, DecodingKey::from_rsa(public)?))
+}
+
+/// Generate a random HMAC secret of `len` bytes, usable with the HS* algorithms
+pub fn generate_hmac(len: usize) -> (EncodingKey, DecodingKey) {
+    let mut secret = vec![0; len];
+    rand::thread_rng().fill_bytes(&mut secret);
+    (EncodingKey::from_hmac_secret(&secret), DecodingKey::from_hmac_secret(&secret))
+}
+
+/// `curve` is named like the JWK `crv` member: `P-256`, `P-384` or `P-521`.
+/// EC keys aren't supported by this crate, so those return `ErrorKind::UnsupportedKeyType`,
+/// and any other name `ErrorKind::InvalidKeyFormat`.
+pub fn generate_ec(curve: &str) -> Result<(EncodingKey, DecodingKey)> {
+    match curve {
+        "P-256" | "P-384" | "P-521" => Err(new_error(ErrorKind::UnsupportedKeyType)),
+        _ => Err(new_error(ErrorKind::InvalidKeyFormat)),
+    }
+}
+
+/// Ed25519 keys aren't supported by this crate, this always returns
+/// `ErrorKind::UnsupportedKeyType`.
+pub fn generate_ed25519() -> Result<(EncodingKey, DecodingKey)> {
+    Err(new_error(ErrorKind::UnsupportedKeyType))
+}
@@ -0,0 +1,54 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// A UTC timestamp in seconds, the type of the `exp`, `nbf` and `iat` claims.
+/// It is serialized as a plain JSON number.
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use jsonwebtoken_rustcrypto::Timestamp;
+///
+/// #[derive(Debug, Serialize, Deserialize)]
+/// struct Claims {
+///     sub: String,
+///     iat: Timestamp,
+///     exp: Timestamp,
+/// }
+///
+/// let claims = Claims { sub: "b@b.com".to_owned(), iat: Timestamp::now(), exp: Timestamp::in_seconds(3600) };
+/// assert!(claims.iat < claims.exp);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Timestamp(pub u64);
+
+impl Timestamp {
+    /// The current time
+    pub fn now() -> Self {
+        Timestamp::from(SystemTime::now())
+    }
+
+    /// The time `seconds` from now, for example an `exp` claim. Saturates at `u64::MAX`.
+    pub fn in_seconds(seconds: u64) -> Self {
+        Timestamp(Timestamp::now().0.saturating_add(seconds))
+    }
+
+    /// The number of seconds since the epoch
+    pub fn as_secs(self) -> u64 {
+        self.0
+    }
+}
+
+impl From<SystemTime> for Timestamp {
+    /// Times before the epoch become `0`
+    fn from(time: SystemTime) -> Self {
+        Timestamp(time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs())
+    }
+}
+
+impl From<Timestamp> for SystemTime {
+    fn from(timestamp: Timestamp) -> Self {
+        UNIX_EPOCH + Duration::from_secs(timestamp.0)
+    }
+}
@@ -1,15 +1,19 @@
 use std::collections::HashSet;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use serde::Deserialize;
 use serde_json::map::Map;
-use serde_json::{from_value, Value};
+use serde_json::Value;
 
 use crate::algorithms::Algorithm;
 use crate::errors::{new_error, ErrorKind, Result};
+use crate::header::Header;
 
 /// Contains the various validations that are applied after decoding a JWT.
 ///
-/// All time validation happen on UTC timestamps as seconds.
+/// All time validation happen on UTC timestamps as seconds, unless `time_unit` says otherwise.
 ///
 /// ```rust
 /// use jsonwebtoken_rustcrypto::Validation;
@@ -32,38 +36,189 @@ pub struct Validation {
     ///
     /// Defaults to `0`.
     pub leeway: u64,
+    /// Leeway (in seconds) for the `exp` validation only, overriding `leeway` when set.
+    ///
+    /// Defaults to `None`.
+    pub exp_leeway: Option<u64>,
+    /// Leeway (in seconds) for the `nbf` validation only, overriding `leeway` when set.
+    ///
+    /// Defaults to `None`.
+    pub nbf_leeway: Option<u64>,
+    /// Leeway (in seconds) for the `iat` validation only, overriding `leeway` when set.
+    ///
+    /// Defaults to `None`.
+    pub iat_leeway: Option<u64>,
     /// Whether to validate the `exp` field.
     ///
     /// It will return an error if the time in the `exp` field is past.
     ///
     /// Defaults to `true`.
     pub validate_exp: bool,
+    /// How long (in seconds) after expiring, on top of the leeway, a token is still accepted, for
+    /// refresh endpoints. Such tokens are flagged with `TokenData::expired_but_in_grace`.
+    ///
+    /// Defaults to `None`.
+    pub expiry_grace: Option<u64>,
+    /// The claims that have to be present, `ErrorKind::MissingRequiredClaim` is returned
+    /// otherwise. `exp`, `nbf` and `iat` are read from their claim paths. When `exp` or `nbf` is
+    /// also validated, a missing one is reported as `ExpiredSignature` or `ImmatureSignature`.
+    ///
+    /// Defaults to `{"exp"}`.
+    pub required_spec_claims: HashSet<String>,
     /// Whether to validate the `nbf` field.
     ///
     /// It will return an error if the current timestamp is before the time in the `nbf` field.
     ///
     /// Defaults to `false`.
     pub validate_nbf: bool,
+    /// Whether to validate the `aud` field against `aud`, when set.
+    ///
+    /// Turning it off skips the check without having to remove the configured audience.
+    ///
+    /// Defaults to `true`.
+    pub validate_aud: bool,
+    /// Whether tokens without an `aud` field are rejected when `aud` is validated.
+    /// Turning it off only checks the audience of tokens that have one.
+    ///
+    /// Defaults to `true`.
+    pub aud_required: bool,
     /// If it contains a value, the validation will check that the `aud` field is a member of the
     /// audience provided and will error otherwise.
     ///
     /// Defaults to `None`.
     pub aud: Option<HashSet<String>>,
+    /// Whether the `aud` field is compared to `aud` ignoring ASCII case.
+    ///
+    /// Defaults to `false`, as audiences are case-sensitive per the spec.
+    pub aud_case_insensitive: bool,
     /// If it contains a value, the validation will check that the `iss` field is the same as the
     /// one provided and will error otherwise.
     ///
     /// Defaults to `None`.
     pub iss: Option<String>,
+    /// Whether the `iss` field is compared to `iss` ignoring ASCII case, for providers that
+    /// vary the case of their issuer URL.
+    ///
+    /// Defaults to `false`, as issuers are case-sensitive per the spec.
+    pub iss_case_insensitive: bool,
     /// If it contains a value, the validation will check that the `sub` field is the same as the
     /// one provided and will error otherwise.
     ///
     /// Defaults to `None`.
     pub sub: Option<String>,
+    /// If it contains a value, the validation will check that the `azp` (authorized party) field
+    /// is the same as the one provided and will error otherwise.
+    ///
+    /// Defaults to `None`.
+    pub azp: Option<String>,
+    /// The OAuth scopes the token must all have, read from the space delimited `scope` claim or
+    /// the `scp` array, `ErrorKind::InsufficientScope` is returned otherwise.
+    ///
+    /// Defaults to empty.
+    pub required_scopes: HashSet<String>,
     /// If it contains a value, the validation will check that the `alg` of the header is contained
     /// in the ones provided and will error otherwise.
+    /// When empty, any algorithm working with the key is accepted, e.g. a HS512 token is verified
+    /// as such with a secret meant for HS256, so set it to the algorithms you actually use.
     ///
     /// Defaults to empty.
     pub algorithms: Vec<Algorithm>,
+    /// Algorithms that are still accepted but that the caller wants to move away from.
+    /// Tokens using one of them are decoded as usual and flagged with
+    /// `TokenData::used_discouraged_algorithm`.
+    ///
+    /// Defaults to empty.
+    pub discouraged_algorithms: Vec<Algorithm>,
+    /// Whether to reject tokens whose header contains parameters this crate doesn't know about,
+    /// unless they are listed in the `crit` header.
+    ///
+    /// Defaults to `false`.
+    pub forbid_unknown_header_fields: bool,
+    /// Whether to accept tokens whose parts are base64url encoded with `=` padding, as some
+    /// lenient encoders emit. This is not canonical, RFC7515 forbids padding.
+    ///
+    /// Defaults to `false`.
+    pub allow_base64_padding: bool,
+    /// If it contains a value, the validation will check that the `kid` of the header is the same
+    /// as the one provided and will error otherwise.
+    ///
+    /// Defaults to `None`.
+    pub required_kid: Option<String>,
+    /// If it contains a value, the validation will check that the header has a `typ` and that it
+    /// is one of these, ignoring ASCII case, and will error otherwise.
+    ///
+    /// Defaults to `None`.
+    pub allowed_typ: Option<HashSet<String>>,
+    /// Where to find the `exp` claim, as a list of object keys starting from the top level claims.
+    ///
+    /// Defaults to `["exp"]`.
+    pub exp_claim_path: Vec<String>,
+    /// Where to find the `nbf` claim, as a list of object keys starting from the top level claims.
+    ///
+    /// Defaults to `["nbf"]`.
+    pub nbf_claim_path: Vec<String>,
+    /// Where to find the `iat` claim, as a list of object keys starting from the top level claims.
+    ///
+    /// Defaults to `["iat"]`.
+    pub iat_claim_path: Vec<String>,
+    /// Whether to check that `exp` is strictly after `nbf` and `iat` when they are present.
+    ///
+    /// It will return an error if the token could never have been valid.
+    ///
+    /// Defaults to `false`.
+    pub validate_claim_consistency: bool,
+    /// The unit of the `exp`, `nbf` and `iat` claims. Milliseconds are not standard, but some
+    /// issuers use them. The leeways stay in seconds.
+    ///
+    /// Defaults to `TimeUnit::Seconds`.
+    pub time_unit: TimeUnit,
+    /// Whether to run all the claims checks and return every failure at once, in an
+    /// `ErrorKind::Multiple`, instead of stopping at the first one. A single failure is still
+    /// returned as is.
+    ///
+    /// Defaults to `false`.
+    pub collect_all_errors: bool,
+    /// Whether to validate the claims even when the header or the signature are wrong, and return
+    /// the same `ErrorKind::InvalidToken` for every failure, so attackers can't tell a bad
+    /// signature from bad claims. The claims are then parsed before being verified. The claims
+    /// checks still stop at the first failure, unless `collect_all_errors` is set.
+    ///
+    /// Defaults to `false`.
+    pub uniform_errors: bool,
+    /// If it contains a value, it is called with the claims after all the other checks passed,
+    /// an `Err` is returned as `ErrorKind::CustomValidation` with its message.
+    ///
+    /// Defaults to `None`.
+    pub custom_validator: Option<CustomValidator>,
+}
+
+/// The unit of the time claims, see `Validation::time_unit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeUnit {
+    /// Seconds since the epoch, as RFC7519 requires
+    Seconds,
+    /// Milliseconds since the epoch
+    Millis,
+}
+
+/// The closure of a [CustomValidator](struct.CustomValidator.html)
+pub type ClaimsValidatorFn = Arc<dyn Fn(&Value) -> std::result::Result<(), String> + Send + Sync>;
+
+/// A business rule on the claims of a token, run by `Validation::custom_validator`.
+/// Two validators are only equal if they are the same closure.
+#[derive(Clone)]
+pub struct CustomValidator(pub ClaimsValidatorFn);
+
+impl fmt::Debug for CustomValidator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("CustomValidator")
+    }
+}
+
+impl PartialEq for CustomValidator {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
 }
 
 impl Validation {
@@ -72,50 +227,569 @@ impl Validation {
         Validation { algorithms: vec![alg], ..Default::default() }
     }
 
+    /// Create a validation setup that only checks the signature and that the alg is the given one.
+    /// No time, audience, issuer or subject claim is looked at, handy for tokens without an `exp`.
+    pub fn signature_only(alg: Algorithm) -> Validation {
+        Validation {
+            validate_exp: false,
+            validate_nbf: false,
+            required_spec_claims: HashSet::new(),
+            ..Validation::new(alg)
+        }
+    }
+
+    /// Returns a copy of the validation changed by `tweak`, for per-request variations of a
+    /// shared base:
+    ///
+    /// ```rust
+    /// use jsonwebtoken_rustcrypto::{Validation, Algorithm};
+    ///
+    /// let base = Validation::new(Algorithm::RS256).with_issuer("https://example.com");
+    /// let validation = base.clone_with(|v| v.set_audience(&["tenant-1"]));
+    /// ```
+    pub fn clone_with<F: FnOnce(&mut Validation)>(&self, tweak: F) -> Validation {
+        let mut validation = self.clone();
+        tweak(&mut validation);
+        validation
+    }
+
     /// `aud` is a collection of one or more acceptable audience members
     pub fn set_audience<T: ToString>(&mut self, items: &[T]) {
         self.aud = Some(items.iter().map(|x| x.to_string()).collect())
     }
+
+    /// Replace the claims that have to be present, use an empty slice to accept tokens without `exp`
+    pub fn set_required_spec_claims<T: ToString>(&mut self, items: &[T]) {
+        self.required_spec_claims = items.iter().map(|x| x.to_string()).collect()
+    }
+
+    /// Whether to check the `aud` claim against the configured audience
+    pub fn set_validate_aud(&mut self, validate: bool) {
+        self.validate_aud = validate
+    }
+
+    /// Whether a missing `aud` claim fails the audience check
+    pub fn set_aud_required(&mut self, required: bool) {
+        self.aud_required = required
+    }
+
+    /// Whether to ignore ASCII case when comparing the `aud` claim
+    pub fn set_audience_case_insensitive(&mut self, case_insensitive: bool) {
+        self.aud_case_insensitive = case_insensitive
+    }
+
+    /// Whether to ignore ASCII case when comparing the `iss` claim
+    pub fn set_issuer_case_insensitive(&mut self, case_insensitive: bool) {
+        self.iss_case_insensitive = case_insensitive
+    }
+
+    /// Only accept tokens whose `azp` claim is the given one, as defined by
+    /// [OpenID Connect](https://openid.net/specs/openid-connect-core-1_0.html#IDToken)
+    pub fn set_authorized_party<T: ToString>(&mut self, azp: T) {
+        self.azp = Some(azp.to_string())
+    }
+
+    /// Leeway applied to `exp` instead of the global `leeway`
+    pub fn set_exp_leeway(&mut self, leeway: u64) {
+        self.exp_leeway = Some(leeway)
+    }
+
+    /// Leeway applied to `nbf` instead of the global `leeway`
+    pub fn set_nbf_leeway(&mut self, leeway: u64) {
+        self.nbf_leeway = Some(leeway)
+    }
+
+    /// Leeway applied to `iat` instead of the global `leeway`
+    pub fn set_iat_leeway(&mut self, leeway: u64) {
+        self.iat_leeway = Some(leeway)
+    }
+
+    /// Accept tokens that expired less than `grace` ago, rounded down to whole seconds
+    pub fn set_expiry_grace(&mut self, grace: Duration) {
+        self.expiry_grace = Some(grace.as_secs())
+    }
+
+    /// Only accept tokens that have all the given scopes
+    pub fn set_required_scopes<T: ToString>(&mut self, scopes: &[T]) {
+        self.required_scopes = scopes.iter().map(|x| x.to_string()).collect()
+    }
+
+    /// Replace the allowed algorithms, see `Algorithm::rsa_all` and friends to allow a family
+    pub fn set_algorithms(&mut self, algorithms: &[Algorithm]) {
+        self.algorithms = algorithms.to_vec()
+    }
+
+    /// Flag, without rejecting them, tokens signed with one of the given algorithms
+    pub fn set_discouraged_algorithms(&mut self, algorithms: &[Algorithm]) {
+        self.discouraged_algorithms = algorithms.to_vec()
+    }
+
+    /// Reject tokens with unknown header parameters that aren't listed in `crit`
+    pub fn set_forbid_unknown_header_fields(&mut self, forbid: bool) {
+        self.forbid_unknown_header_fields = forbid
+    }
+
+    /// Accept tokens whose parts have `=` padding
+    pub fn set_allow_base64_padding(&mut self, allow: bool) {
+        self.allow_base64_padding = allow
+    }
+
+    /// Only accept tokens whose header `kid` is the given one
+    pub fn set_required_kid<T: ToString>(&mut self, kid: T) {
+        self.required_kid = Some(kid.to_string())
+    }
+
+    /// Only accept tokens whose header `typ` is one of `types`, e.g. `&["JWT", "at+jwt"]`
+    pub fn set_allowed_typ<T: ToString>(&mut self, types: &[T]) {
+        self.allowed_typ = Some(types.iter().map(|x| x.to_string()).collect())
+    }
+
+    /// Reject tokens whose `exp` is not after their `nbf` or `iat`
+    pub fn set_validate_claim_consistency(&mut self, validate: bool) {
+        self.validate_claim_consistency = validate
+    }
+
+    /// Read the time claims in `unit` instead of seconds
+    pub fn set_time_unit(&mut self, unit: TimeUnit) {
+        self.time_unit = unit
+    }
+
+    /// Report all the claims validation failures instead of only the first one
+    pub fn set_collect_all_errors(&mut self, collect: bool) {
+        self.collect_all_errors = collect
+    }
+
+    /// Validate the claims even on a signature failure and return the same generic error whatever
+    /// failed
+    pub fn set_uniform_errors(&mut self, uniform: bool) {
+        self.uniform_errors = uniform
+    }
+
+    /// Run `validator` on the claims as the last check, to enforce rules this crate doesn't know
+    /// about, like a token having a given scope.
+    ///
+    /// A rejection is returned as `ErrorKind::CustomValidation` with the validator's message, not
+    /// as `ErrorKind::InvalidToken`: that one has no room for a message, and adding one would
+    /// break everyone matching on it.
+    pub fn set_custom_validator(&mut self, validator: ClaimsValidatorFn) {
+        self.custom_validator = Some(CustomValidator(validator))
+    }
+
+    /// Read the time claims from somewhere other than the top level, for example when an
+    /// issuer nests them under a namespace: `set_time_claim_paths(&["https://ns", "exp"], ...)`
+    pub fn set_time_claim_paths<T: ToString>(&mut self, exp: &[T], nbf: &[T], iat: &[T]) {
+        self.exp_claim_path = exp.iter().map(|x| x.to_string()).collect();
+        self.nbf_claim_path = nbf.iter().map(|x| x.to_string()).collect();
+        self.iat_claim_path = iat.iter().map(|x| x.to_string()).collect();
+    }
+}
+
+/// Chainable versions of the setters, to configure a `Validation` in one expression:
+///
+/// ```rust
+/// use jsonwebtoken_rustcrypto::{Validation, Algorithm};
+///
+/// let validation = Validation::new(Algorithm::RS256)
+///     .with_leeway(5)
+///     .with_issuer("https://example.com")
+///     .with_audience(&["my-app"]);
+/// ```
+impl Validation {
+    /// Set the `leeway` applied to all time claims
+    pub fn with_leeway(mut self, leeway: u64) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    /// See [set_exp_leeway](#method.set_exp_leeway)
+    pub fn with_exp_leeway(mut self, leeway: u64) -> Self {
+        self.set_exp_leeway(leeway);
+        self
+    }
+
+    /// See [set_nbf_leeway](#method.set_nbf_leeway)
+    pub fn with_nbf_leeway(mut self, leeway: u64) -> Self {
+        self.set_nbf_leeway(leeway);
+        self
+    }
+
+    /// See [set_iat_leeway](#method.set_iat_leeway)
+    pub fn with_iat_leeway(mut self, leeway: u64) -> Self {
+        self.set_iat_leeway(leeway);
+        self
+    }
+
+    /// See [set_expiry_grace](#method.set_expiry_grace)
+    pub fn with_expiry_grace(mut self, grace: Duration) -> Self {
+        self.set_expiry_grace(grace);
+        self
+    }
+
+    /// Whether to validate the `exp` claim
+    pub fn with_validate_exp(mut self, validate: bool) -> Self {
+        self.validate_exp = validate;
+        self
+    }
+
+    /// See [set_required_spec_claims](#method.set_required_spec_claims)
+    pub fn with_required_spec_claims<T: ToString>(mut self, items: &[T]) -> Self {
+        self.set_required_spec_claims(items);
+        self
+    }
+
+    /// Whether to validate the `nbf` claim
+    pub fn with_validate_nbf(mut self, validate: bool) -> Self {
+        self.validate_nbf = validate;
+        self
+    }
+
+    /// See [set_audience](#method.set_audience)
+    pub fn with_audience<T: ToString>(mut self, items: &[T]) -> Self {
+        self.set_audience(items);
+        self
+    }
+
+    /// See [set_validate_aud](#method.set_validate_aud)
+    pub fn with_validate_aud(mut self, validate: bool) -> Self {
+        self.set_validate_aud(validate);
+        self
+    }
+
+    /// See [set_aud_required](#method.set_aud_required)
+    pub fn with_aud_required(mut self, required: bool) -> Self {
+        self.set_aud_required(required);
+        self
+    }
+
+    /// See [set_audience_case_insensitive](#method.set_audience_case_insensitive)
+    pub fn with_audience_case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.set_audience_case_insensitive(case_insensitive);
+        self
+    }
+
+    /// Only accept tokens whose `iss` claim is the given one
+    pub fn with_issuer<T: ToString>(mut self, iss: T) -> Self {
+        self.iss = Some(iss.to_string());
+        self
+    }
+
+    /// See [set_issuer_case_insensitive](#method.set_issuer_case_insensitive)
+    pub fn with_issuer_case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.set_issuer_case_insensitive(case_insensitive);
+        self
+    }
+
+    /// Only accept tokens whose `sub` claim is the given one
+    pub fn with_subject<T: ToString>(mut self, sub: T) -> Self {
+        self.sub = Some(sub.to_string());
+        self
+    }
+
+    /// See [set_authorized_party](#method.set_authorized_party)
+    pub fn with_authorized_party<T: ToString>(mut self, azp: T) -> Self {
+        self.set_authorized_party(azp);
+        self
+    }
+
+    /// See [set_required_scopes](#method.set_required_scopes)
+    pub fn with_required_scopes<T: ToString>(mut self, scopes: &[T]) -> Self {
+        self.set_required_scopes(scopes);
+        self
+    }
+
+    /// See [set_algorithms](#method.set_algorithms)
+    pub fn with_algorithms(mut self, algorithms: &[Algorithm]) -> Self {
+        self.set_algorithms(algorithms);
+        self
+    }
+
+    /// See [set_discouraged_algorithms](#method.set_discouraged_algorithms)
+    pub fn with_discouraged_algorithms(mut self, algorithms: &[Algorithm]) -> Self {
+        self.set_discouraged_algorithms(algorithms);
+        self
+    }
+
+    /// See [set_forbid_unknown_header_fields](#method.set_forbid_unknown_header_fields)
+    pub fn with_forbid_unknown_header_fields(mut self, forbid: bool) -> Self {
+        self.set_forbid_unknown_header_fields(forbid);
+        self
+    }
+
+    /// See [set_allow_base64_padding](#method.set_allow_base64_padding)
+    pub fn with_allow_base64_padding(mut self, allow: bool) -> Self {
+        self.set_allow_base64_padding(allow);
+        self
+    }
+
+    /// See [set_required_kid](#method.set_required_kid)
+    pub fn with_required_kid<T: ToString>(mut self, kid: T) -> Self {
+        self.set_required_kid(kid);
+        self
+    }
+
+    /// See [set_allowed_typ](#method.set_allowed_typ)
+    pub fn with_allowed_typ<T: ToString>(mut self, types: &[T]) -> Self {
+        self.set_allowed_typ(types);
+        self
+    }
+
+    /// See [set_validate_claim_consistency](#method.set_validate_claim_consistency)
+    pub fn with_validate_claim_consistency(mut self, validate: bool) -> Self {
+        self.set_validate_claim_consistency(validate);
+        self
+    }
+
+    /// See [set_time_unit](#method.set_time_unit)
+    pub fn with_time_unit(mut self, unit: TimeUnit) -> Self {
+        self.set_time_unit(unit);
+        self
+    }
+
+    /// See [set_collect_all_errors](#method.set_collect_all_errors)
+    pub fn with_collect_all_errors(mut self, collect: bool) -> Self {
+        self.set_collect_all_errors(collect);
+        self
+    }
+
+    /// See [set_uniform_errors](#method.set_uniform_errors)
+    pub fn with_uniform_errors(mut self, uniform: bool) -> Self {
+        self.set_uniform_errors(uniform);
+        self
+    }
+
+    /// See [set_custom_validator](#method.set_custom_validator)
+    pub fn with_custom_validator(mut self, validator: ClaimsValidatorFn) -> Self {
+        self.set_custom_validator(validator);
+        self
+    }
+
+    /// See [set_time_claim_paths](#method.set_time_claim_paths)
+    pub fn with_time_claim_paths<T: ToString>(mut self, exp: &[T], nbf: &[T], iat: &[T]) -> Self {
+        self.set_time_claim_paths(exp, nbf, iat);
+        self
+    }
 }
 
 impl Default for Validation {
     fn default() -> Validation {
         Validation {
             leeway: 0,
+            exp_leeway: None,
+            nbf_leeway: None,
+            iat_leeway: None,
 
             validate_exp: true,
+            expiry_grace: None,
+            required_spec_claims: ["exp".to_string()].into_iter().collect(),
             validate_nbf: false,
+            validate_aud: true,
+            aud_required: true,
 
             iss: None,
+            iss_case_insensitive: false,
             sub: None,
+            azp: None,
+            required_scopes: HashSet::new(),
             aud: None,
+            aud_case_insensitive: false,
 
             algorithms: Vec::new(),
+            discouraged_algorithms: Vec::new(),
+
+            forbid_unknown_header_fields: false,
+            allow_base64_padding: false,
+            required_kid: None,
+            allowed_typ: None,
+
+            exp_claim_path: vec!["exp".to_string()],
+            nbf_claim_path: vec!["nbf".to_string()],
+            iat_claim_path: vec!["iat".to_string()],
+
+            validate_claim_consistency: false,
+
+            time_unit: TimeUnit::Seconds,
+            collect_all_errors: false,
+            uniform_errors: false,
+
+            custom_validator: None,
         }
     }
 }
 
+/// Compares two claim values, ignoring ASCII case if asked to
+fn claim_eq(a: &str, b: &str, case_insensitive: bool) -> bool {
+    if case_insensitive {
+        a.eq_ignore_ascii_case(b)
+    } else {
+        a == b
+    }
+}
+
 fn get_current_timestamp() -> u64 {
     let start = SystemTime::now();
     start.duration_since(UNIX_EPOCH).expect("Time went backwards").as_secs()
 }
 
-pub fn validate(claims: &Map<String, Value>, options: &Validation) -> Result<()> {
+/// Follows `path` through nested objects, returning `None` if any key is missing
+fn claim_at<'a>(claims: &'a Map<String, Value>, path: &[String]) -> Option<&'a Value> {
+    let (first, rest) = path.split_first()?;
+    rest.iter().try_fold(claims.get(first)?, |value, key| value.get(key))
+}
+
+/// Reads a time claim in `options.time_unit` as seconds since the epoch.
+/// Some languages serialize timestamps as floats, those are truncated to whole seconds.
+fn timestamp(value: &Value, options: &Validation) -> Result<u64> {
+    let divisor = match options.time_unit {
+        TimeUnit::Seconds => 1,
+        TimeUnit::Millis => 1000,
+    };
+    match value.as_f64() {
+        Some(time) if value.is_f64() && time.is_finite() && time >= 0.0 => {
+            Ok(time as u64 / divisor)
+        }
+        _ => Ok(u64::deserialize(value)? / divisor),
+    }
+}
+
+pub fn validate_header(header: &Header, options: &Validation) -> Result<()> {
+    if options.forbid_unknown_header_fields {
+        let crit = header.crit.as_deref().unwrap_or_default();
+        if header.extras.keys().any(|name| !crit.contains(name)) {
+            return Err(new_error(ErrorKind::InvalidToken));
+        }
+    }
+
+    if let Some(ref required_kid) = options.required_kid {
+        if header.kid.as_ref() != Some(required_kid) {
+            return Err(new_error(ErrorKind::InvalidToken));
+        }
+    }
+
+    if let Some(ref allowed_typ) = options.allowed_typ {
+        match header.typ {
+            Some(ref typ)
+                if allowed_typ.iter().any(|allowed| allowed.eq_ignore_ascii_case(typ)) => {}
+            _ => return Err(new_error(ErrorKind::InvalidToken)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Validate the claims of a payload, which have to be a JSON object. They are given as a `Value`
+/// so the custom validator can be handed them without copying.
+pub fn validate(claims: &Value, options: &Validation) -> Result<()> {
+    validate_at(claims, options, get_current_timestamp())
+}
+
+/// Like `validate`, also returning whether the claims were only accepted thanks to
+/// `expiry_grace`. Both are decided at the same instant.
+pub(crate) fn validate_with_grace(claims: &Value, options: &Validation) -> Result<bool> {
     let now = get_current_timestamp();
+    validate_at(claims, options, now)?;
+    Ok(expired_but_in_grace(claims_object(claims)?, options, now))
+}
+
+fn validate_at(payload: &Value, options: &Validation, now: u64) -> Result<()> {
+    let claims = claims_object(payload)?;
+    // The claims checks, in the order they run, only the time ones need `now`
+    let checks: [&dyn Fn() -> Result<()>; 10] = [
+        &|| check_required(claims, options),
+        &|| check_exp(claims, options, now),
+        &|| check_nbf(claims, options, now),
+        &|| check_claim_consistency(claims, options),
+        &|| check_iss(claims, options),
+        &|| check_sub(claims, options),
+        &|| check_aud(claims, options),
+        &|| check_azp(claims, options),
+        &|| check_scopes(claims, options),
+        &|| check_custom(payload, options),
+    ];
+
+    if !options.collect_all_errors {
+        return checks.iter().try_for_each(|check| check());
+    }
 
+    let mut errors: Vec<ErrorKind> =
+        checks.iter().filter_map(|check| check().err()).map(|err| err.into_kind()).collect();
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(new_error(errors.remove(0))),
+        _ => Err(new_error(ErrorKind::Multiple(errors))),
+    }
+}
+
+/// The claims of a payload, as they have to be a JSON object
+fn claims_object(payload: &Value) -> Result<&Map<String, Value>> {
+    payload.as_object().ok_or_else(|| new_error(ErrorKind::InvalidToken))
+}
+
+fn check_required(claims: &Map<String, Value>, options: &Validation) -> Result<()> {
+    for name in &options.required_spec_claims {
+        let missing = match name.as_str() {
+            "exp" if options.validate_exp => claim_at(claims, &options.exp_claim_path)
+                .is_none()
+                .then_some(ErrorKind::ExpiredSignature),
+            "nbf" if options.validate_nbf => claim_at(claims, &options.nbf_claim_path)
+                .is_none()
+                .then_some(ErrorKind::ImmatureSignature),
+            "exp" => claim_at(claims, &options.exp_claim_path)
+                .is_none()
+                .then(|| ErrorKind::MissingRequiredClaim(name.clone())),
+            "nbf" => claim_at(claims, &options.nbf_claim_path)
+                .is_none()
+                .then(|| ErrorKind::MissingRequiredClaim(name.clone())),
+            "iat" => claim_at(claims, &options.iat_claim_path)
+                .is_none()
+                .then(|| ErrorKind::MissingRequiredClaim(name.clone())),
+            _ => {
+                (!claims.contains_key(name)).then(|| ErrorKind::MissingRequiredClaim(name.clone()))
+            }
+        };
+        if let Some(kind) = missing {
+            return Err(new_error(kind));
+        }
+    }
+
+    Ok(())
+}
+
+/// The earliest `exp` accepted without the grace period
+fn exp_threshold(options: &Validation, now: u64) -> u64 {
+    now.saturating_sub(options.exp_leeway.unwrap_or(options.leeway))
+}
+
+fn check_exp(claims: &Map<String, Value>, options: &Validation, now: u64) -> Result<()> {
     if options.validate_exp {
-        if let Some(exp) = claims.get("exp") {
-            if from_value::<u64>(exp.clone())? < now - options.leeway {
+        if let Some(exp) = claim_at(claims, &options.exp_claim_path) {
+            let threshold =
+                exp_threshold(options, now).saturating_sub(options.expiry_grace.unwrap_or(0));
+            if timestamp(exp, options)? < threshold {
                 return Err(new_error(ErrorKind::ExpiredSignature));
             }
-        } else {
-            return Err(new_error(ErrorKind::ExpiredSignature));
         }
     }
 
+    Ok(())
+}
+
+/// Whether claims that passed validation at `now` were only accepted thanks to `expiry_grace`
+fn expired_but_in_grace(claims: &Map<String, Value>, options: &Validation, now: u64) -> bool {
+    if !options.validate_exp || options.expiry_grace.is_none() {
+        return false;
+    }
+    match claim_at(claims, &options.exp_claim_path).map(|exp| timestamp(exp, options)) {
+        Some(Ok(exp)) => exp < exp_threshold(options, now),
+        _ => false,
+    }
+}
+
+fn check_nbf(claims: &Map<String, Value>, options: &Validation, now: u64) -> Result<()> {
     if options.validate_nbf {
-        if let Some(nbf) = claims.get("nbf") {
-            if from_value::<u64>(nbf.clone())? > now + options.leeway {
+        if let Some(nbf) = claim_at(claims, &options.nbf_claim_path) {
+            if timestamp(nbf, options)?
+                > now.saturating_add(options.nbf_leeway.unwrap_or(options.leeway))
+            {
                 return Err(new_error(ErrorKind::ImmatureSignature));
             }
         } else {
@@ -123,9 +797,30 @@ pub fn validate(claims: &Map<String, Value>, options: &Validation) -> Result<()>
         }
     }
 
+    Ok(())
+}
+
+fn check_claim_consistency(claims: &Map<String, Value>, options: &Validation) -> Result<()> {
+    if options.validate_claim_consistency {
+        if let Some(exp) = claim_at(claims, &options.exp_claim_path) {
+            let exp = timestamp(exp, options)?;
+            for path in [&options.nbf_claim_path, &options.iat_claim_path] {
+                if let Some(start) = claim_at(claims, path) {
+                    if exp <= timestamp(start, options)? {
+                        return Err(new_error(ErrorKind::InvalidToken));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn check_iss(claims: &Map<String, Value>, options: &Validation) -> Result<()> {
     if let Some(ref correct_iss) = options.iss {
         if let Some(iss) = claims.get("iss") {
-            if from_value::<String>(iss.clone())? != *correct_iss {
+            if !claim_eq(<&str>::deserialize(iss)?, correct_iss, options.iss_case_insensitive) {
                 return Err(new_error(ErrorKind::InvalidIssuer));
             }
         } else {
@@ -133,9 +828,13 @@ pub fn validate(claims: &Map<String, Value>, options: &Validation) -> Result<()>
         }
     }
 
+    Ok(())
+}
+
+fn check_sub(claims: &Map<String, Value>, options: &Validation) -> Result<()> {
     if let Some(ref correct_sub) = options.sub {
         if let Some(sub) = claims.get("sub") {
-            if from_value::<String>(sub.clone())? != *correct_sub {
+            if <&str>::deserialize(sub)? != correct_sub {
                 return Err(new_error(ErrorKind::InvalidSubject));
             }
         } else {
@@ -143,70 +842,376 @@ pub fn validate(claims: &Map<String, Value>, options: &Validation) -> Result<()>
         }
     }
 
-    if let Some(ref correct_aud) = options.aud {
+    Ok(())
+}
+
+fn check_aud(claims: &Map<String, Value>, options: &Validation) -> Result<()> {
+    if let (true, Some(ref correct_aud)) = (options.validate_aud, &options.aud) {
+        let is_correct_aud = |aud: &str| {
+            if options.aud_case_insensitive {
+                correct_aud.iter().any(|correct| claim_eq(correct, aud, true))
+            } else {
+                correct_aud.contains(aud)
+            }
+        };
         if let Some(aud) = claims.get("aud") {
             match aud {
                 Value::String(aud_found) => {
-                    if !correct_aud.contains(aud_found) {
+                    if !is_correct_aud(aud_found) {
                         return Err(new_error(ErrorKind::InvalidAudience));
                     }
                 }
-                Value::Array(_) => {
-                    let provided_aud: HashSet<String> = from_value(aud.clone())?;
-                    if provided_aud.intersection(correct_aud).count() == 0 {
+                Value::Array(provided_aud) => {
+                    let mut found = false;
+                    for aud in provided_aud {
+                        found |= is_correct_aud(<&str>::deserialize(aud)?);
+                    }
+                    if !found {
                         return Err(new_error(ErrorKind::InvalidAudience));
                     }
                 }
                 _ => return Err(new_error(ErrorKind::InvalidAudience)),
             };
-        } else {
+        } else if options.aud_required {
             return Err(new_error(ErrorKind::InvalidAudience));
         }
     }
 
-    Ok(())
-}
+    Ok(())
+}
+
+fn check_azp(claims: &Map<String, Value>, options: &Validation) -> Result<()> {
+    if let Some(ref correct_azp) = options.azp {
+        match claims.get("azp") {
+            Some(azp) if <&str>::deserialize(azp)? == correct_azp => (),
+            _ => return Err(new_error(ErrorKind::InvalidToken)),
+        }
+    }
+
+    Ok(())
+}
+
+fn check_scopes(claims: &Map<String, Value>, options: &Validation) -> Result<()> {
+    if options.required_scopes.is_empty() {
+        return Ok(());
+    }
+
+    let mut scopes = HashSet::new();
+    for name in ["scope", "scp"] {
+        match claims.get(name) {
+            Some(Value::String(scope)) => scopes.extend(scope.split(' ')),
+            Some(Value::Array(scope)) => {
+                for scope in scope {
+                    scopes.insert(<&str>::deserialize(scope)?);
+                }
+            }
+            Some(_) => return Err(new_error(ErrorKind::InsufficientScope)),
+            None => (),
+        }
+    }
+    if !options.required_scopes.iter().all(|scope| scopes.contains(scope.as_str())) {
+        return Err(new_error(ErrorKind::InsufficientScope));
+    }
+
+    Ok(())
+}
+
+fn check_custom(payload: &Value, options: &Validation) -> Result<()> {
+    if let Some(CustomValidator(ref validator)) = options.custom_validator {
+        validator(payload).map_err(|message| new_error(ErrorKind::CustomValidation(message)))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use serde_json::map::Map;
+    use serde_json::{json, to_value, Value};
+
+    use std::time::Duration;
+
+    use super::{
+        expired_but_in_grace, get_current_timestamp, validate_at, validate_header,
+        validate_with_grace, TimeUnit, Validation,
+    };
+
+    use crate::errors::{ErrorKind, Result};
+    use crate::header::Header;
+
+    /// Most tests build their claims as a map
+    fn validate(claims: &Map<String, Value>, options: &Validation) -> Result<()> {
+        super::validate(&Value::Object(claims.clone()), options)
+    }
+
+    #[test]
+    fn exp_in_future_ok() {
+        let mut claims = Map::new();
+        claims.insert("exp".to_string(), to_value(get_current_timestamp() + 10000).unwrap());
+        let res = validate(&claims, &Validation::default());
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn exp_in_past_fails() {
+        let mut claims = Map::new();
+        claims.insert("exp".to_string(), to_value(get_current_timestamp() - 100000).unwrap());
+        let res = validate(&claims, &Validation::default());
+        assert!(res.is_err());
+
+        match res.unwrap_err().kind() {
+            ErrorKind::ExpiredSignature => (),
+            _ => unreachable!(),
+        };
+    }
+
+    #[test]
+    fn clone_with_keeps_base() {
+        let base = Validation::default().with_issuer("issuer").with_audience(&["base"]);
+        let per_request = base.clone_with(|v| v.set_audience(&["tenant-1"]));
+
+        let claims =
+            json!({"exp": get_current_timestamp() + 100, "iss": "issuer", "aud": "tenant-1"});
+        let claims = serde_json::from_value(claims).unwrap();
+        assert!(validate(&claims, &per_request).is_ok());
+        match validate(&claims, &base).unwrap_err().kind() {
+            ErrorKind::InvalidAudience => (),
+            t => panic!("{:?}", t),
+        };
+        assert_eq!(per_request.iss, base.iss);
+        assert_eq!(base.aud, Some(["base".to_string()].into_iter().collect()));
+    }
+
+    #[test]
+    fn scopes_from_string() {
+        let claims = json!({"exp": get_current_timestamp() + 100, "scope": "read write admin"});
+        let validation = Validation::default().with_required_scopes(&["admin", "read"]);
+        assert!(validate(&serde_json::from_value(claims).unwrap(), &validation).is_ok());
+    }
+
+    #[test]
+    fn scopes_from_array() {
+        let claims = json!({"exp": get_current_timestamp() + 100, "scp": ["read", "admin"]});
+        let validation = Validation::default().with_required_scopes(&["admin", "read"]);
+        assert!(validate(&serde_json::from_value(claims).unwrap(), &validation).is_ok());
+    }
+
+    #[test]
+    fn missing_scope_fails() {
+        let validation = Validation::default().with_required_scopes(&["admin", "read"]);
+        for claims in [
+            json!({"exp": get_current_timestamp() + 100, "scope": "read write"}),
+            json!({"exp": get_current_timestamp() + 100, "scp": ["read"]}),
+            json!({"exp": get_current_timestamp() + 100}),
+        ] {
+            let res = validate(&serde_json::from_value(claims).unwrap(), &validation);
+            match res.unwrap_err().kind() {
+                ErrorKind::InsufficientScope => (),
+                t => panic!("{:?}", t),
+            };
+        }
+    }
+
+    #[test]
+    fn exp_in_grace_ok() {
+        let mut claims = Map::new();
+        claims.insert("exp".to_string(), to_value(get_current_timestamp() - 120).unwrap());
+        let validation = Validation::default().with_expiry_grace(Duration::from_secs(300));
+        assert!(validate_with_grace(&Value::Object(claims.clone()), &validation).unwrap());
+
+        claims.insert("exp".to_string(), to_value(get_current_timestamp() - 600).unwrap());
+        match validate(&claims, &validation).unwrap_err().kind() {
+            ErrorKind::ExpiredSignature => (),
+            t => panic!("{:?}", t),
+        };
+
+        claims.insert("exp".to_string(), to_value(get_current_timestamp() + 100).unwrap());
+        assert!(!validate_with_grace(&Value::Object(claims.clone()), &validation).unwrap());
+    }
+
+    #[test]
+    fn grace_flag_uses_the_validation_time() {
+        let now = 1_000_000;
+        let mut claims = Map::new();
+        // Exactly at the edge of the leeway
+        claims.insert("exp".to_string(), to_value(now - 60).unwrap());
+        let validation =
+            Validation::default().with_leeway(60).with_expiry_grace(Duration::from_secs(300));
+        assert!(validate_at(&Value::Object(claims.clone()), &validation, now).is_ok());
+        assert!(!expired_but_in_grace(&claims, &validation, now));
+        assert!(expired_but_in_grace(&claims, &validation, now + 1));
+    }
+
+    #[test]
+    fn huge_leeway_does_not_overflow() {
+        let mut claims = Map::new();
+        claims.insert("exp".to_string(), to_value(i64::MAX).unwrap());
+        claims.insert("nbf".to_string(), to_value(i64::MAX).unwrap());
+        let validation = Validation { leeway: u64::MAX, validate_nbf: true, ..Default::default() };
+        assert!(validate(&claims, &validation).is_ok());
+
+        claims.insert("nbf".to_string(), to_value(u64::MAX).unwrap());
+        let res = validate(&claims, &Validation { leeway: 0, ..validation });
+        match res.unwrap_err().kind() {
+            ErrorKind::ImmatureSignature => (),
+            t => panic!("{:?}", t),
+        };
+    }
+
+    #[test]
+    fn millisecond_time_claims() {
+        let now_ms = get_current_timestamp() * 1000;
+        let validation = Validation::default().with_time_unit(TimeUnit::Millis);
+        let claims = json!({"exp": now_ms + 60_000, "nbf": now_ms - 60_000, "iat": now_ms});
+        assert!(validate(claims.as_object().unwrap(), &validation).is_ok());
+
+        // Read as seconds, a millisecond `exp` is thousands of years away and never expires
+        let expired = json!({"exp": now_ms - 60_000});
+        assert!(validate(expired.as_object().unwrap(), &Validation::default()).is_ok());
+        match validate(expired.as_object().unwrap(), &validation).unwrap_err().kind() {
+            ErrorKind::ExpiredSignature => (),
+            t => panic!("{:?}", t),
+        };
+
+        // And an `exp` in seconds read as milliseconds is in 1970
+        let seconds = json!({"exp": get_current_timestamp() + 60});
+        match validate(seconds.as_object().unwrap(), &validation).unwrap_err().kind() {
+            ErrorKind::ExpiredSignature => (),
+            t => panic!("{:?}", t),
+        };
+    }
+
+    #[test]
+    fn exp_in_past_but_in_leeway_ok() {
+        let mut claims = Map::new();
+        claims.insert("exp".to_string(), to_value(get_current_timestamp() - 500).unwrap());
+        let validation = Validation { leeway: 1000 * 60, ..Default::default() };
+        let res = validate(&claims, &validation);
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn aud_not_validated_when_disabled() {
+        let mut claims = Map::new();
+        claims.insert("aud".to_string(), to_value(["Everyone"]).unwrap());
+        let mut validation = Validation {
+            validate_exp: false,
+            required_spec_claims: HashSet::new(),
+            ..Validation::default()
+        };
+        validation.set_audience(&["None"]);
+        validation.set_validate_aud(false);
+        assert!(validate(&claims, &validation).is_ok());
+        assert!(validate(&Map::new(), &validation).is_ok());
+    }
+
+    #[test]
+    fn aud_validated_when_enabled() {
+        let mut claims = Map::new();
+        claims.insert("aud".to_string(), to_value(["Everyone"]).unwrap());
+        let mut validation = Validation {
+            validate_exp: false,
+            required_spec_claims: HashSet::new(),
+            ..Validation::default()
+        };
+        validation.set_audience(&["Everyone"]);
+        validation.set_validate_aud(true);
+        assert!(validate(&claims, &validation).is_ok());
 
-#[cfg(test)]
-mod tests {
-    use serde_json::map::Map;
-    use serde_json::to_value;
+        validation.set_audience(&["None"]);
+        match validate(&claims, &validation).unwrap_err().kind() {
+            ErrorKind::InvalidAudience => (),
+            _ => unreachable!(),
+        };
+    }
 
-    use super::{get_current_timestamp, validate, Validation};
+    #[test]
+    fn aud_required_setting() {
+        let mut with_aud = Map::new();
+        with_aud.insert("aud".to_string(), to_value("Everyone").unwrap());
+        let mut validation = Validation {
+            validate_exp: false,
+            required_spec_claims: HashSet::new(),
+            ..Validation::default()
+        };
+        validation.set_audience(&["Everyone"]);
 
-    use crate::errors::ErrorKind;
+        for required in [true, false] {
+            validation.set_aud_required(required);
+            assert!(validate(&with_aud, &validation).is_ok());
 
-    #[test]
-    fn exp_in_future_ok() {
-        let mut claims = Map::new();
-        claims.insert("exp".to_string(), to_value(get_current_timestamp() + 10000).unwrap());
-        let res = validate(&claims, &Validation::default());
-        assert!(res.is_ok());
+            let res = validate(&Map::new(), &validation);
+            if required {
+                match res.unwrap_err().kind() {
+                    ErrorKind::InvalidAudience => (),
+                    _ => unreachable!(),
+                };
+            } else {
+                assert!(res.is_ok());
+            }
+        }
+
+        // A present but wrong `aud` is still rejected
+        let mut wrong_aud = Map::new();
+        wrong_aud.insert("aud".to_string(), to_value("Nobody").unwrap());
+        assert!(validate(&wrong_aud, &validation).is_err());
     }
 
+    // https://github.com/Keats/jsonwebtoken/issues/51
     #[test]
-    fn exp_in_past_fails() {
+    fn required_spec_claims() {
+        let validation = Validation::default().with_required_spec_claims(&["exp", "iss"]);
         let mut claims = Map::new();
-        claims.insert("exp".to_string(), to_value(get_current_timestamp() - 100000).unwrap());
-        let res = validate(&claims, &Validation::default());
-        assert!(res.is_err());
+        claims.insert("exp".to_string(), to_value(get_current_timestamp() + 100).unwrap());
+        match validate(&claims, &validation).unwrap_err().kind() {
+            ErrorKind::MissingRequiredClaim(name) => assert_eq!(name, "iss"),
+            t => panic!("{:?}", t),
+        };
 
-        match res.unwrap_err().kind() {
+        claims.insert("iss".to_string(), to_value("anyone").unwrap());
+        assert!(validate(&claims, &validation).is_ok());
+
+        // `exp` is still validated when present, but no longer required
+        let validation = Validation::default().with_required_spec_claims::<&str>(&[]);
+        assert!(validate(&Map::new(), &validation).is_ok());
+        claims.insert("exp".to_string(), to_value(1).unwrap());
+        match validate(&claims, &validation).unwrap_err().kind() {
             ErrorKind::ExpiredSignature => (),
-            _ => unreachable!(),
+            t => panic!("{:?}", t),
         };
     }
 
     #[test]
-    fn exp_in_past_but_in_leeway_ok() {
-        let mut claims = Map::new();
-        claims.insert("exp".to_string(), to_value(get_current_timestamp() - 500).unwrap());
-        let validation = Validation { leeway: 1000 * 60, ..Default::default() };
-        let res = validate(&claims, &validation);
-        assert!(res.is_ok());
+    fn required_nbf_without_validate_nbf() {
+        let mut validation = Validation::default().with_required_spec_claims(&["nbf"]);
+        validation.validate_nbf = false;
+        match validate(&Map::new(), &validation).unwrap_err().kind() {
+            ErrorKind::MissingRequiredClaim(name) => assert_eq!(name, "nbf"),
+            t => panic!("{:?}", t),
+        };
+
+        // Only its presence is checked, not its value
+        let claims = json!({"nbf": get_current_timestamp() + 1000});
+        assert!(validate(claims.as_object().unwrap(), &validation).is_ok());
+    }
+
+    #[test]
+    fn required_iat_follows_claim_path() {
+        let validation = Validation::default()
+            .with_required_spec_claims(&["iat"])
+            .with_time_claim_paths(&["exp"], &["nbf"], &["ns", "iat"]);
+        let claims = json!({"exp": get_current_timestamp() + 100, "ns": {"iat": 1}});
+        assert!(validate(claims.as_object().unwrap(), &validation).is_ok());
+
+        let claims = json!({"exp": get_current_timestamp() + 100, "iat": 1});
+        match validate(claims.as_object().unwrap(), &validation).unwrap_err().kind() {
+            ErrorKind::MissingRequiredClaim(name) => assert_eq!(name, "iat"),
+            t => panic!("{:?}", t),
+        };
     }
 
-    // https://github.com/Keats/jsonwebtoken/issues/51
     #[test]
     fn validation_called_even_if_field_is_empty() {
         let claims = Map::new();
@@ -222,8 +1227,12 @@ mod tests {
     fn nbf_in_past_ok() {
         let mut claims = Map::new();
         claims.insert("nbf".to_string(), to_value(get_current_timestamp() - 10000).unwrap());
-        let validation =
-            Validation { validate_exp: false, validate_nbf: true, ..Validation::default() };
+        let validation = Validation {
+            validate_exp: false,
+            required_spec_claims: HashSet::new(),
+            validate_nbf: true,
+            ..Validation::default()
+        };
         let res = validate(&claims, &validation);
         assert!(res.is_ok());
     }
@@ -232,8 +1241,12 @@ mod tests {
     fn nbf_in_future_fails() {
         let mut claims = Map::new();
         claims.insert("nbf".to_string(), to_value(get_current_timestamp() + 100000).unwrap());
-        let validation =
-            Validation { validate_exp: false, validate_nbf: true, ..Validation::default() };
+        let validation = Validation {
+            validate_exp: false,
+            required_spec_claims: HashSet::new(),
+            validate_nbf: true,
+            ..Validation::default()
+        };
         let res = validate(&claims, &validation);
         assert!(res.is_err());
 
@@ -251,6 +1264,7 @@ mod tests {
             leeway: 1000 * 60,
             validate_nbf: true,
             validate_exp: false,
+            required_spec_claims: HashSet::new(),
             ..Default::default()
         };
         let res = validate(&claims, &validation);
@@ -263,6 +1277,7 @@ mod tests {
         claims.insert("iss".to_string(), to_value("Keats").unwrap());
         let validation = Validation {
             validate_exp: false,
+            required_spec_claims: HashSet::new(),
             iss: Some("Keats".to_string()),
             ..Default::default()
         };
@@ -270,12 +1285,32 @@ mod tests {
         assert!(res.is_ok());
     }
 
+    #[test]
+    fn iss_case_sensitivity() {
+        let mut claims = Map::new();
+        claims.insert("iss".to_string(), to_value("https://Example.com").unwrap());
+        let validation = Validation {
+            validate_exp: false,
+            required_spec_claims: HashSet::new(),
+            iss: Some("https://example.com".to_string()),
+            ..Default::default()
+        };
+        match validate(&claims, &validation).unwrap_err().kind() {
+            ErrorKind::InvalidIssuer => (),
+            t => panic!("{:?}", t),
+        };
+
+        let validation = validation.with_issuer_case_insensitive(true);
+        assert!(validate(&claims, &validation).is_ok());
+    }
+
     #[test]
     fn iss_not_matching_fails() {
         let mut claims = Map::new();
         claims.insert("iss".to_string(), to_value("Hacked").unwrap());
         let validation = Validation {
             validate_exp: false,
+            required_spec_claims: HashSet::new(),
             iss: Some("Keats".to_string()),
             ..Default::default()
         };
@@ -293,6 +1328,7 @@ mod tests {
         let claims = Map::new();
         let validation = Validation {
             validate_exp: false,
+            required_spec_claims: HashSet::new(),
             iss: Some("Keats".to_string()),
             ..Default::default()
         };
@@ -311,6 +1347,7 @@ mod tests {
         claims.insert("sub".to_string(), to_value("Keats").unwrap());
         let validation = Validation {
             validate_exp: false,
+            required_spec_claims: HashSet::new(),
             sub: Some("Keats".to_string()),
             ..Default::default()
         };
@@ -324,6 +1361,7 @@ mod tests {
         claims.insert("sub".to_string(), to_value("Hacked").unwrap());
         let validation = Validation {
             validate_exp: false,
+            required_spec_claims: HashSet::new(),
             sub: Some("Keats".to_string()),
             ..Default::default()
         };
@@ -341,6 +1379,7 @@ mod tests {
         let claims = Map::new();
         let validation = Validation {
             validate_exp: false,
+            required_spec_claims: HashSet::new(),
             sub: Some("Keats".to_string()),
             ..Default::default()
         };
@@ -357,7 +1396,11 @@ mod tests {
     fn aud_string_ok() {
         let mut claims = Map::new();
         claims.insert("aud".to_string(), to_value(["Everyone"]).unwrap());
-        let mut validation = Validation { validate_exp: false, ..Validation::default() };
+        let mut validation = Validation {
+            validate_exp: false,
+            required_spec_claims: HashSet::new(),
+            ..Validation::default()
+        };
         validation.set_audience(&["Everyone"]);
         let res = validate(&claims, &validation);
         assert!(res.is_ok());
@@ -367,17 +1410,106 @@ mod tests {
     fn aud_array_of_string_ok() {
         let mut claims = Map::new();
         claims.insert("aud".to_string(), to_value(["UserA", "UserB"]).unwrap());
-        let mut validation = Validation { validate_exp: false, ..Validation::default() };
+        let mut validation = Validation {
+            validate_exp: false,
+            required_spec_claims: HashSet::new(),
+            ..Validation::default()
+        };
         validation.set_audience(&["UserA", "UserB"]);
         let res = validate(&claims, &validation);
         assert!(res.is_ok());
     }
 
+    #[test]
+    fn aud_case_sensitivity() {
+        let mut claims = Map::new();
+        claims.insert("aud".to_string(), to_value(["https://Example.com"]).unwrap());
+        let mut validation = Validation {
+            validate_exp: false,
+            required_spec_claims: HashSet::new(),
+            ..Validation::default()
+        };
+        validation.set_audience(&["https://example.com"]);
+        match validate(&claims, &validation).unwrap_err().kind() {
+            ErrorKind::InvalidAudience => (),
+            t => panic!("{:?}", t),
+        };
+
+        validation.set_audience_case_insensitive(true);
+        assert!(validate(&claims, &validation).is_ok());
+        claims.insert("aud".to_string(), to_value("HTTPS://EXAMPLE.COM").unwrap());
+        assert!(validate(&claims, &validation).is_ok());
+    }
+
+    #[test]
+    fn collect_all_errors() {
+        let mut claims = Map::new();
+        claims.insert("exp".to_string(), to_value(get_current_timestamp() - 1000).unwrap());
+        claims.insert("aud".to_string(), to_value("Someone else").unwrap());
+        let validation = Validation::default().with_audience(&["Me"]);
+        match validate(&claims, &validation).unwrap_err().kind() {
+            ErrorKind::ExpiredSignature => (),
+            t => panic!("{:?}", t),
+        };
+
+        let validation = validation.with_collect_all_errors(true);
+        match validate(&claims, &validation).unwrap_err().kind() {
+            ErrorKind::Multiple(errors) => match errors.as_slice() {
+                [ErrorKind::ExpiredSignature, ErrorKind::InvalidAudience] => (),
+                t => panic!("{:?}", t),
+            },
+            t => panic!("{:?}", t),
+        };
+
+        claims.insert("aud".to_string(), to_value("Me").unwrap());
+        match validate(&claims, &validation).unwrap_err().kind() {
+            ErrorKind::ExpiredSignature => (),
+            t => panic!("{:?}", t),
+        };
+    }
+
+    #[test]
+    fn azp_validation() {
+        let validation = Validation::default()
+            .with_validate_exp(false)
+            .with_required_spec_claims::<&str>(&[])
+            .with_authorized_party("app");
+        let mut claims = Map::new();
+        match validate(&claims, &validation).unwrap_err().kind() {
+            ErrorKind::InvalidToken => (),
+            t => panic!("{:?}", t),
+        };
+
+        claims.insert("azp".to_string(), to_value("other-app").unwrap());
+        match validate(&claims, &validation).unwrap_err().kind() {
+            ErrorKind::InvalidToken => (),
+            t => panic!("{:?}", t),
+        };
+
+        claims.insert("azp".to_string(), to_value("app").unwrap());
+        assert!(validate(&claims, &validation).is_ok());
+        // Not checked unless asked for
+        claims.insert("azp".to_string(), to_value("other-app").unwrap());
+        assert!(validate(
+            &claims,
+            &Validation {
+                validate_exp: false,
+                required_spec_claims: HashSet::new(),
+                ..Default::default()
+            }
+        )
+        .is_ok());
+    }
+
     #[test]
     fn aud_type_mismatch_fails() {
         let mut claims = Map::new();
         claims.insert("aud".to_string(), to_value(["Everyone"]).unwrap());
-        let mut validation = Validation { validate_exp: false, ..Validation::default() };
+        let mut validation = Validation {
+            validate_exp: false,
+            required_spec_claims: HashSet::new(),
+            ..Validation::default()
+        };
         validation.set_audience(&["UserA", "UserB"]);
         let res = validate(&claims, &validation);
         assert!(res.is_err());
@@ -392,7 +1524,11 @@ mod tests {
     fn aud_correct_type_not_matching_fails() {
         let mut claims = Map::new();
         claims.insert("aud".to_string(), to_value(["Everyone"]).unwrap());
-        let mut validation = Validation { validate_exp: false, ..Validation::default() };
+        let mut validation = Validation {
+            validate_exp: false,
+            required_spec_claims: HashSet::new(),
+            ..Validation::default()
+        };
         validation.set_audience(&["None"]);
         let res = validate(&claims, &validation);
         assert!(res.is_err());
@@ -406,7 +1542,11 @@ mod tests {
     #[test]
     fn aud_missing_fails() {
         let claims = Map::new();
-        let mut validation = Validation { validate_exp: false, ..Validation::default() };
+        let mut validation = Validation {
+            validate_exp: false,
+            required_spec_claims: HashSet::new(),
+            ..Validation::default()
+        };
         validation.set_audience(&["None"]);
         let res = validate(&claims, &validation);
         assert!(res.is_err());
@@ -451,10 +1591,216 @@ mod tests {
         let mut aud_hashset = std::collections::HashSet::new();
         aud_hashset.insert(aud);
 
-        let validation =
-            Validation { aud: Some(aud_hashset), validate_exp: false, ..Validation::default() };
+        let validation = Validation {
+            aud: Some(aud_hashset),
+            validate_exp: false,
+            required_spec_claims: HashSet::new(),
+            ..Validation::default()
+        };
         let res = validate(&claims, &validation);
         println!("{:?}", res);
         assert!(res.is_ok());
     }
+
+    #[test]
+    fn unknown_header_field_allowed_by_default() {
+        let mut header = Header::default();
+        header.extras.insert("foo".to_string(), to_value("bar").unwrap());
+        let res = validate_header(&header, &Validation::default());
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn unknown_header_field_forbidden_fails() {
+        let mut header = Header::default();
+        header.extras.insert("foo".to_string(), to_value("bar").unwrap());
+        let mut validation = Validation::default();
+        validation.set_forbid_unknown_header_fields(true);
+        let res = validate_header(&header, &validation);
+        assert!(res.is_err());
+
+        match res.unwrap_err().kind() {
+            ErrorKind::InvalidToken => (),
+            _ => unreachable!(),
+        };
+
+        header.crit = Some(vec!["foo".to_string()]);
+        let res = validate_header(&header, &validation);
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn required_kid_matching_ok() {
+        let header = Header { kid: Some("key-1".to_string()), ..Header::default() };
+        let mut validation = Validation::default();
+        validation.set_required_kid("key-1");
+        let res = validate_header(&header, &validation);
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn required_kid_mismatch_or_missing_fails() {
+        let mut validation = Validation::default();
+        validation.set_required_kid("key-1");
+
+        for kid in [Some("key-2".to_string()), None] {
+            let header = Header { kid, ..Header::default() };
+            match validate_header(&header, &validation).unwrap_err().kind() {
+                ErrorKind::InvalidToken => (),
+                _ => unreachable!(),
+            };
+        }
+    }
+
+    #[test]
+    fn allowed_typ_matching_ok() {
+        let validation = Validation::default().with_allowed_typ(&["JWT", "at+jwt"]);
+        for typ in ["JWT", "AT+JWT", "at+jwt"] {
+            let header = Header { typ: Some(typ.to_string()), ..Header::default() };
+            assert!(validate_header(&header, &validation).is_ok());
+        }
+    }
+
+    #[test]
+    fn allowed_typ_set_through_field_ignores_case() {
+        let validation = Validation {
+            allowed_typ: Some(["JWT".to_string(), "At+Jwt".to_string()].into()),
+            ..Default::default()
+        };
+        for typ in ["jwt", "JWT", "at+JWT"] {
+            let header = Header { typ: Some(typ.to_string()), ..Header::default() };
+            assert!(validate_header(&header, &validation).is_ok());
+        }
+    }
+
+    #[test]
+    fn allowed_typ_other_or_missing_fails() {
+        let validation = Validation::default().with_allowed_typ(&["JWT", "at+jwt"]);
+        for typ in [Some("dpop+jwt".to_string()), None] {
+            let header = Header { typ, ..Header::default() };
+            match validate_header(&header, &validation).unwrap_err().kind() {
+                ErrorKind::InvalidToken => (),
+                _ => unreachable!(),
+            };
+        }
+    }
+
+    #[test]
+    fn exp_float_in_future_ok() {
+        let mut claims = Map::new();
+        claims.insert("exp".to_string(), json!((get_current_timestamp() + 10000) as f64 + 0.75));
+        let res = validate(&claims, &Validation::default());
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn exp_float_in_past_fails() {
+        let mut claims = Map::new();
+        claims.insert("exp".to_string(), json!((get_current_timestamp() - 100000) as f64));
+        let res = validate(&claims, &Validation::default());
+        assert!(res.is_err());
+
+        match res.unwrap_err().kind() {
+            ErrorKind::ExpiredSignature => (),
+            _ => unreachable!(),
+        };
+    }
+
+    #[test]
+    fn nested_exp_ok() {
+        let mut claims = Map::new();
+        claims.insert(
+            "https://ns".to_string(),
+            json!({ "exp": get_current_timestamp() + 10000, "nbf": get_current_timestamp() - 10 }),
+        );
+        let mut validation = Validation { validate_nbf: true, ..Validation::default() };
+        validation.set_time_claim_paths(
+            &["https://ns", "exp"],
+            &["https://ns", "nbf"],
+            &["https://ns", "iat"],
+        );
+        let res = validate(&claims, &validation);
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn nested_exp_in_past_fails() {
+        let mut claims = Map::new();
+        claims.insert("exp".to_string(), to_value(get_current_timestamp() + 10000).unwrap());
+        claims.insert("https://ns".to_string(), json!({ "exp": get_current_timestamp() - 10000 }));
+        let mut validation = Validation::default();
+        validation.set_time_claim_paths(&["https://ns", "exp"], &["nbf"], &["iat"]);
+        let res = validate(&claims, &validation);
+        assert!(res.is_err());
+
+        match res.unwrap_err().kind() {
+            ErrorKind::ExpiredSignature => (),
+            _ => unreachable!(),
+        };
+    }
+
+    #[test]
+    fn exp_and_nbf_leeway_independent() {
+        let mut claims = Map::new();
+        claims.insert("exp".to_string(), to_value(get_current_timestamp() - 500).unwrap());
+        claims.insert("nbf".to_string(), to_value(get_current_timestamp() + 500).unwrap());
+        let mut validation = Validation { validate_nbf: true, ..Validation::default() };
+        validation.set_exp_leeway(1000);
+        let res = validate(&claims, &validation);
+        match res.unwrap_err().kind() {
+            ErrorKind::ImmatureSignature => (),
+            t => panic!("{:?}", t),
+        };
+
+        validation.set_nbf_leeway(1000);
+        assert!(validate(&claims, &validation).is_ok());
+
+        // The global leeway is ignored once a specific one is set
+        let validation = Validation {
+            leeway: 1000,
+            nbf_leeway: Some(0),
+            validate_nbf: true,
+            ..Validation::default()
+        };
+        let res = validate(&claims, &validation);
+        match res.unwrap_err().kind() {
+            ErrorKind::ImmatureSignature => (),
+            t => panic!("{:?}", t),
+        };
+    }
+
+    #[test]
+    fn exp_before_iat_inconsistent_fails() {
+        let mut claims = Map::new();
+        claims.insert("exp".to_string(), to_value(get_current_timestamp() + 100).unwrap());
+        claims.insert("iat".to_string(), to_value(get_current_timestamp() + 200).unwrap());
+        let mut validation = Validation::default();
+        assert!(validate(&claims, &validation).is_ok());
+
+        validation.set_validate_claim_consistency(true);
+        let res = validate(&claims, &validation);
+        match res.unwrap_err().kind() {
+            ErrorKind::InvalidToken => (),
+            t => panic!("{:?}", t),
+        };
+    }
+
+    #[test]
+    fn ordered_time_claims_consistent_ok() {
+        let mut claims = Map::new();
+        claims.insert("iat".to_string(), to_value(get_current_timestamp() - 100).unwrap());
+        claims.insert("nbf".to_string(), to_value(get_current_timestamp() - 100).unwrap());
+        claims.insert("exp".to_string(), to_value(get_current_timestamp() + 100).unwrap());
+        let mut validation = Validation::default();
+        validation.set_validate_claim_consistency(true);
+        assert!(validate(&claims, &validation).is_ok());
+    }
+
+    // A single `Validation` is meant to be shared between threads, anything added to it
+    // (callbacks included) must keep it that way
+    #[test]
+    fn validation_is_clone_send_sync() {
+        fn assert_clone_send_sync<T: Clone + Send + Sync>() {}
+        assert_clone_send_sync::<Validation>();
+    }
 }
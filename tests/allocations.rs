@@ -0,0 +1,86 @@
+//! Checks that the validation config is only borrowed by `decode`: a `Validation` with every
+//! check configured must not cause more allocations than a minimal one.
+//! This lives in its own test binary as it replaces the global allocator.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use chrono::Utc;
+use jsonwebtoken_rustcrypto::{
+    decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use serde::{Deserialize, Serialize};
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    sub: String,
+    iss: String,
+    aud: Vec<String>,
+    exp: i64,
+    nbf: i64,
+}
+
+fn count_allocations<F: FnOnce()>(f: F) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn validation_config_does_not_allocate() {
+    let now = Utc::now().timestamp();
+    let claims = Claims {
+        sub: "b@b.com".to_string(),
+        iss: "issuer".to_string(),
+        aud: vec!["other".to_string(), "audience".to_string()],
+        exp: now + 10000,
+        nbf: now - 10,
+    };
+    let token =
+        encode(&Header::default(), &claims, &EncodingKey::from_hmac_secret(b"secret")).unwrap();
+    let key = DecodingKey::from_hmac_secret(b"secret");
+
+    let minimal = Validation::new(Algorithm::HS256);
+    let mut everything = Validation {
+        algorithms: vec![Algorithm::HS512, Algorithm::HS384, Algorithm::HS256],
+        validate_nbf: true,
+        validate_claim_consistency: true,
+        iss: Some("issuer".to_string()),
+        sub: Some("b@b.com".to_string()),
+        ..Validation::default()
+    };
+    let audience: Vec<String> =
+        (0..100).map(|i| format!("audience-{}", i)).chain(["audience".to_string()]).collect();
+    everything.set_audience(&audience);
+
+    // Warm up anything lazily initialised on first use
+    decode::<Claims>(&token, &key, &minimal).unwrap();
+    decode::<Claims>(&token, &key, &everything).unwrap();
+
+    let minimal_count = count_allocations(|| {
+        decode::<Claims>(&token, &key, &minimal).unwrap();
+    });
+    let everything_count = count_allocations(|| {
+        decode::<Claims>(&token, &key, &everything).unwrap();
+    });
+    assert_eq!(minimal_count, everything_count);
+}
@@ -0,0 +1,96 @@
+use jsonwebtoken_rustcrypto::custom::{self, AlgorithmRegistry, JwsAlgorithm};
+use jsonwebtoken_rustcrypto::errors::{ErrorKind, Result};
+use jsonwebtoken_rustcrypto::{Header, Validation};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    sub: String,
+    company: String,
+    exp: i64,
+}
+
+/// Not a real algorithm: the signature is the message reversed, xored with the key
+struct ReverseXor(u8);
+
+impl JwsAlgorithm for ReverseXor {
+    fn jwa_name(&self) -> &str {
+        "X-REVERSE"
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>> {
+        Ok(message.iter().rev().map(|b| b ^ self.0).collect())
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> Result<bool> {
+        Ok(self.sign(message)? == signature)
+    }
+}
+
+fn claims() -> Claims {
+    Claims {
+        sub: "b@b.com".to_string(),
+        company: "ACME".to_string(),
+        exp: chrono::Utc::now().timestamp() + 10000,
+    }
+}
+
+#[test]
+fn round_trip_custom_algorithm() {
+    let mut registry = AlgorithmRegistry::new();
+    registry.register(ReverseXor(42)).unwrap();
+
+    let token = custom::encode(&Header::default(), &claims(), &ReverseXor(42)).unwrap();
+    let token_data = custom::decode::<Claims>(&token, &registry, &Validation::default()).unwrap();
+    assert_eq!(claims(), token_data.claims);
+    assert_eq!(token_data.header["alg"], "X-REVERSE");
+    assert_eq!(token_data.header["typ"], "JWT");
+}
+
+#[test]
+fn custom_algorithm_wrong_key() {
+    let mut registry = AlgorithmRegistry::new();
+    registry.register(ReverseXor(1)).unwrap();
+
+    let token = custom::encode(&Header::default(), &claims(), &ReverseXor(42)).unwrap();
+    let err = custom::decode::<Claims>(&token, &registry, &Validation::default()).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidSignature), "{:?}", err);
+}
+
+#[test]
+fn custom_algorithm_not_registered() {
+    let token = custom::encode(&Header::default(), &claims(), &ReverseXor(42)).unwrap();
+    let err = custom::decode::<Claims>(&token, &AlgorithmRegistry::new(), &Validation::default())
+        .unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidAlgorithm), "{:?}", err);
+}
+
+#[test]
+fn custom_algorithm_cannot_shadow_builtin() {
+    struct Fake;
+    impl JwsAlgorithm for Fake {
+        fn jwa_name(&self) -> &str {
+            "HS256"
+        }
+        fn sign(&self, _: &[u8]) -> Result<Vec<u8>> {
+            Ok(Vec::new())
+        }
+        fn verify(&self, _: &[u8], _: &[u8]) -> Result<bool> {
+            Ok(true)
+        }
+    }
+
+    let err = AlgorithmRegistry::new().register(Fake).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidAlgorithmName), "{:?}", err);
+}
+
+#[test]
+fn custom_algorithm_validates_claims() {
+    let mut registry = AlgorithmRegistry::new();
+    registry.register(ReverseXor(42)).unwrap();
+
+    let expired = Claims { exp: 1, ..claims() };
+    let token = custom::encode(&Header::default(), &expired, &ReverseXor(42)).unwrap();
+    let err = custom::decode::<Claims>(&token, &registry, &Validation::default()).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::ExpiredSignature), "{:?}", err);
+}
@@ -1,2 +1,5 @@
 // mod ecdsa;
+mod custom;
 mod rsa;
+#[cfg(feature = "testkeys")]
+mod testkeys;
@@ -1,7 +1,14 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use chrono::Utc;
+use jsonwebtoken_rustcrypto::errors::ErrorKind;
+use jsonwebtoken_rustcrypto::jwk::JWK;
 use jsonwebtoken_rustcrypto::{
-    crypto::{sign, verify},
-    decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+    crypto::{
+        compute_signature, sign, sign_pss_with_mgf1, verify, verify_pss_with_mgf1, Mgf1Hash,
+        RemoteSigner, RemoteVerifier,
+    },
+    decode, decode_header_ext, encode, Algorithm, AlgorithmFamily, DecodingKey, EncodingKey,
+    Header, Validation,
 };
 use serde::{Deserialize, Serialize};
 
@@ -23,14 +30,29 @@ pub struct Claims {
     exp: i64,
 }
 
-#[test]
-fn round_trip_sign_verification_pem_pkcs1() {
-    let privkey: rsa::RsaPrivateKey =
+/// The claims most tests sign, expiring in a few hours
+fn my_claims() -> Claims {
+    Claims {
+        sub: "b@b.com".to_string(),
+        company: "ACME".to_string(),
+        exp: Utc::now().timestamp() + 10000,
+    }
+}
+
+/// The 2048 bits key pair of `private_rsa_key_pkcs1.pem` and `public_rsa_key_pkcs1.pem`
+fn rsa_keypair() -> (rsa::RsaPrivateKey, rsa::RsaPublicKey) {
+    let privkey =
         rsa::pkcs1::DecodeRsaPrivateKey::from_pkcs1_pem(include_str!("private_rsa_key_pkcs1.pem"))
             .unwrap();
-    let pubkey: rsa::RsaPublicKey =
+    let pubkey =
         rsa::pkcs1::DecodeRsaPublicKey::from_pkcs1_pem(include_str!("public_rsa_key_pkcs1.pem"))
             .unwrap();
+    (privkey, pubkey)
+}
+
+#[test]
+fn round_trip_sign_verification_pem_pkcs1() {
+    let (privkey, pubkey) = rsa_keypair();
 
     for &alg in RSA_ALGORITHMS {
         let encrypted =
@@ -82,17 +104,8 @@ fn round_trip_sign_verification_der() {
 
 #[test]
 fn round_trip_claim() {
-    let my_claims = Claims {
-        sub: "b@b.com".to_string(),
-        company: "ACME".to_string(),
-        exp: Utc::now().timestamp() + 10000,
-    };
-    let privkey: rsa::RsaPrivateKey =
-        rsa::pkcs1::DecodeRsaPrivateKey::from_pkcs1_pem(include_str!("private_rsa_key_pkcs1.pem"))
-            .unwrap();
-    let pubkey: rsa::RsaPublicKey =
-        rsa::pkcs1::DecodeRsaPublicKey::from_pkcs1_pem(include_str!("public_rsa_key_pkcs1.pem"))
-            .unwrap();
+    let my_claims = my_claims();
+    let (privkey, pubkey) = rsa_keypair();
 
     for &alg in RSA_ALGORITHMS {
         let token =
@@ -109,16 +122,39 @@ fn round_trip_claim() {
     }
 }
 
+#[test]
+fn any_rsa_algorithm_accepted_by_rsa_all() {
+    let my_claims = my_claims();
+    let (privkey, pubkey) = rsa_keypair();
+    let mut validation = Validation::default();
+    validation.set_algorithms(Algorithm::rsa_all());
+    assert_eq!(Algorithm::rsa_all(), RSA_ALGORITHMS);
+
+    for &alg in RSA_ALGORITHMS {
+        let token =
+            encode(&Header::new(alg), &my_claims, &EncodingKey::from_rsa(privkey.clone()).unwrap())
+                .unwrap();
+        let token_data =
+            decode::<Claims>(&token, &DecodingKey::from_rsa(pubkey.clone()).unwrap(), &validation)
+                .unwrap();
+        assert_eq!(token_data.header.alg, alg);
+    }
+
+    let token = encode(
+        &Header::new(Algorithm::HS256),
+        &my_claims,
+        &EncodingKey::from_hmac_secret(b"secret"),
+    )
+    .unwrap();
+    let err = decode::<Claims>(&token, &DecodingKey::from_hmac_secret(b"secret"), &validation)
+        .unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidAlgorithm), "{:?}", err);
+}
+
 #[test]
 fn rsa_modulus_exponent() {
-    let privkey =
-        rsa::pkcs1::DecodeRsaPrivateKey::from_pkcs1_pem(include_str!("private_rsa_key_pkcs1.pem"))
-            .unwrap();
-    let my_claims = Claims {
-        sub: "b@b.com".to_string(),
-        company: "ACME".to_string(),
-        exp: Utc::now().timestamp() + 10000,
-    };
+    let (privkey, _) = rsa_keypair();
+    let my_claims = my_claims();
     let n = "yRE6rHuNR0QbHO3H3Kt2pOKGVhQqGZXInOduQNxXzuKlvQTLUTv4l4sggh5_CYYi_cvI-SXVT9kPWSKXxJXBXd_4LkvcPuUakBoAkfh-eiFVMh2VrUyWyj3MFl0HTVF9KwRXLAcwkREiS3npThHRyIxuy0ZMeZfxVL5arMhw1SRELB8HoGfG_AtH89BIE9jDBHZ9dLelK9a184zAf8LwoPLxvJb3Il5nncqPcSfKDDodMFBIMc4lQzDKL5gvmiXLXB1AGLm8KBjfE8s3L5xqi-yUod-j8MtvIj812dkS4QMiRVN_by2h3ZY8LYVGrqZXZTcgn2ujn8uKjXLZVD5TdQ";
     let e = "AQAB";
 
@@ -136,6 +172,36 @@ fn rsa_modulus_exponent() {
     assert!(res.is_ok());
 }
 
+#[test]
+fn rsa_small_exponents() {
+    use rand::SeedableRng;
+    use rsa::traits::PublicKeyParts;
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+    let privkey = rsa::RsaPrivateKey::new_with_exp(&mut rng, 1024, &3u32.into()).unwrap();
+    let n = URL_SAFE_NO_PAD.encode(privkey.n().to_bytes_be());
+    let my_claims = my_claims();
+    let token = encode(
+        &Header::new(Algorithm::RS256),
+        &my_claims,
+        &EncodingKey::from_rsa(privkey).unwrap(),
+    )
+    .unwrap();
+
+    // 3, plain and with a leading zero byte
+    for e in ["Aw", "AAM"] {
+        let key = DecodingKey::from_rsa_components(&n, e).unwrap();
+        let res = decode::<Claims>(&token, &key, &Validation::new(Algorithm::RS256));
+        assert!(res.is_ok(), "{}: {:?}", e, res);
+    }
+
+    // 0 and 1 make degenerate keys
+    for e in ["AA", "AQ", "AAE"] {
+        let err = DecodingKey::from_rsa_components(&n, e).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidKeyFormat), "{}: {:?}", e, err);
+    }
+}
+
 // https://jwt.io/ is often used for examples so ensure their example works with jsonwebtoken
 #[test]
 fn roundtrip_with_jwtio_example_key() {
@@ -144,11 +210,7 @@ fn roundtrip_with_jwtio_example_key() {
     let pubkey: rsa::RsaPublicKey =
         rsa::pkcs8::DecodePublicKey::from_public_key_pem(include_str!("public_jwtio.pem")).unwrap();
 
-    let my_claims = Claims {
-        sub: "b@b.com".to_string(),
-        company: "ACME".to_string(),
-        exp: Utc::now().timestamp() + 10000,
-    };
+    let my_claims = my_claims();
 
     for &alg in RSA_ALGORITHMS {
         let token =
@@ -163,3 +225,567 @@ fn roundtrip_with_jwtio_example_key() {
         assert_eq!(my_claims, token_data.claims);
     }
 }
+
+#[test]
+fn decode_header_ext_rsa_family() {
+    let (privkey, _) = rsa_keypair();
+    let my_claims = my_claims();
+    let token = encode(
+        &Header::new(Algorithm::RS256),
+        &my_claims,
+        &EncodingKey::from_rsa(privkey).unwrap(),
+    )
+    .unwrap();
+
+    let (header, family) = decode_header_ext(&token).unwrap();
+    assert_eq!(header.alg, Algorithm::RS256);
+    assert_eq!(family, AlgorithmFamily::Rsa);
+}
+
+#[test]
+fn round_trip_base64_der() {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use rsa::pkcs8::EncodePublicKey;
+
+    let private_der = STANDARD.encode(include_bytes!("private_rsa_key.der"));
+    let public_der = STANDARD.encode(include_bytes!("public_rsa_key.der"));
+    let pubkey: rsa::RsaPublicKey =
+        rsa::pkcs1::DecodeRsaPublicKey::from_pkcs1_der(include_bytes!("public_rsa_key.der"))
+            .unwrap();
+    let spki_der = STANDARD.encode(pubkey.to_public_key_der().unwrap().as_bytes());
+    let my_claims = my_claims();
+
+    let token = encode(
+        &Header::new(Algorithm::RS256),
+        &my_claims,
+        &EncodingKey::from_rsa_der_base64(&format!("{}\n", private_der)).unwrap(),
+    )
+    .unwrap();
+    for key in [
+        DecodingKey::from_rsa_der_base64(&public_der).unwrap(),
+        DecodingKey::from_spki_der_base64(&spki_der).unwrap(),
+    ] {
+        let token_data =
+            decode::<Claims>(&token, &key, &Validation::new(Algorithm::RS256)).unwrap();
+        assert_eq!(my_claims, token_data.claims);
+    }
+    assert!(DecodingKey::from_spki_der_base64(&public_der).is_err());
+    assert!(EncodingKey::from_rsa_der_base64("not base64!").is_err());
+}
+
+#[test]
+fn round_trip_spki_der() {
+    use rsa::pkcs8::EncodePublicKey;
+
+    let privkey: rsa::RsaPrivateKey =
+        rsa::pkcs8::DecodePrivateKey::from_pkcs8_pem(include_str!("private_rsa_key_pkcs8.pem"))
+            .unwrap();
+    let pubkey: rsa::RsaPublicKey =
+        rsa::pkcs8::DecodePublicKey::from_public_key_pem(include_str!("public_rsa_key_pkcs8.pem"))
+            .unwrap();
+    let spki_der = pubkey.to_public_key_der().unwrap();
+    let my_claims = my_claims();
+
+    let token = encode(
+        &Header::new(Algorithm::RS256),
+        &my_claims,
+        &EncodingKey::from_rsa(privkey).unwrap(),
+    )
+    .unwrap();
+    let token_data = decode::<Claims>(
+        &token,
+        &DecodingKey::from_spki_der(spki_der.as_bytes()).unwrap(),
+        &Validation::new(Algorithm::RS256),
+    )
+    .unwrap();
+    assert_eq!(my_claims, token_data.claims);
+}
+
+#[test]
+fn spki_der_unsupported_key_types() {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    // EC P-256 and Ed25519 public keys
+    for spki in [
+        "MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEw7JAoU/gJbZJvV+zCOvU9yFJq0FNC/edCMRM78P8eQTBCDUTK1ywSYaszvQZvneiW6gNtWEJndSreEcyyUdVvg==",
+        "MCowBQYDK2VwAyEAGb9ECWmEzf6FQbrBZ9w7lshQhqowtrbLDFw4rXAxZuE=",
+    ] {
+        let err = DecodingKey::from_spki_der(&STANDARD.decode(spki).unwrap()).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::UnsupportedKeyType), "{:?}", err);
+    }
+
+    let err = DecodingKey::from_spki_der(b"not der").unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidKeyFormat), "{:?}", err);
+}
+
+#[cfg(feature = "pem")]
+#[test]
+fn round_trip_pem_objects() {
+    let my_claims = my_claims();
+    for (private, public) in [
+        (include_str!("private_rsa_key_pkcs1.pem"), include_str!("public_rsa_key_pkcs1.pem")),
+        (include_str!("private_rsa_key_pkcs8.pem"), include_str!("public_rsa_key_pkcs8.pem")),
+    ] {
+        let encoding_key = EncodingKey::from_pem_object(&pem::parse(private).unwrap()).unwrap();
+        let decoding_key = DecodingKey::from_pem_object(&pem::parse(public).unwrap()).unwrap();
+
+        let token = encode(&Header::new(Algorithm::RS256), &my_claims, &encoding_key).unwrap();
+        let token_data =
+            decode::<Claims>(&token, &decoding_key, &Validation::new(Algorithm::RS256)).unwrap();
+        assert_eq!(my_claims, token_data.claims);
+    }
+}
+
+#[cfg(feature = "pem")]
+#[test]
+fn pem_objects_unsupported_key_types() {
+    for private in
+        [include_str!("../ecdsa/private_ecdsa_key.pem"), include_str!("../ecdsa/private_jwtio.pem")]
+    {
+        let err = EncodingKey::from_pem_object(&pem::parse(private).unwrap()).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::UnsupportedKeyType), "{:?}", err);
+    }
+    let public = pem::parse(include_str!("../ecdsa/public_ecdsa_key.pem")).unwrap();
+    let err = DecodingKey::from_pem_object(&public).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::UnsupportedKeyType), "{:?}", err);
+
+    let certificate = pem::Pem::new("CERTIFICATE", vec![0; 4]);
+    let err = EncodingKey::from_pem_object(&certificate).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidKeyFormat), "{:?}", err);
+    let err = DecodingKey::from_pem_object(&certificate).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidKeyFormat), "{:?}", err);
+}
+
+#[test]
+fn sign_with_private_jwk() {
+    use rsa::traits::{PrivateKeyParts, PublicKeyParts};
+
+    let (privkey, _) = rsa_keypair();
+    let b64 = |n: &rsa::BigUint| URL_SAFE_NO_PAD.encode(n.to_bytes_be());
+    let public = serde_json::json!({
+        "kty": "RSA",
+        "n": b64(privkey.n()),
+        "e": b64(privkey.e()),
+    });
+    let mut private = public.clone();
+    private["d"] = b64(privkey.d()).into();
+    let mut with_primes = private.clone();
+    with_primes["p"] = b64(&privkey.primes()[0]).into();
+    with_primes["q"] = b64(&privkey.primes()[1]).into();
+
+    let my_claims = my_claims();
+    let decoding_key =
+        DecodingKey::from_jwk(&serde_json::from_value::<JWK>(public.clone()).unwrap(), false)
+            .unwrap();
+    for jwk in [private, with_primes] {
+        let encoding_key = EncodingKey::from_jwk(&serde_json::from_value(jwk).unwrap()).unwrap();
+        let token = encode(&Header::new(Algorithm::PS256), &my_claims, &encoding_key).unwrap();
+        let token_data =
+            decode::<Claims>(&token, &decoding_key, &Validation::new(Algorithm::PS256)).unwrap();
+        assert_eq!(my_claims, token_data.claims);
+    }
+
+    let err = EncodingKey::from_jwk(&serde_json::from_value(public).unwrap()).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidRsaKey), "{:?}", err);
+
+    let ec: JWK = serde_json::from_value(serde_json::json!({"kty": "EC", "d": "AQ"})).unwrap();
+    let err = EncodingKey::from_jwk(&ec).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::UnsupportedKeyType), "{:?}", err);
+}
+
+/// Stands for a KMS, signing with a key the caller never sees
+struct MockKms {
+    key: EncodingKey,
+    calls: std::sync::atomic::AtomicUsize,
+}
+
+impl RemoteSigner for MockKms {
+    fn sign(
+        &self,
+        message: &[u8],
+        algorithm: Algorithm,
+    ) -> jsonwebtoken_rustcrypto::Result<Vec<u8>> {
+        self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        compute_signature(message, &self.key, algorithm)
+    }
+}
+
+#[test]
+fn sign_with_remote_signer() {
+    let (privkey, _) = rsa_keypair();
+    let pubkey = privkey.to_public_key();
+    let kms = std::sync::Arc::new(MockKms {
+        key: EncodingKey::from_rsa(privkey).unwrap(),
+        calls: Default::default(),
+    });
+    let remote = EncodingKey::from_remote(kms.clone());
+    let my_claims = my_claims();
+
+    for &alg in RSA_ALGORITHMS {
+        let token = encode(&Header::new(alg), &my_claims, &remote).unwrap();
+        let token_data = decode::<Claims>(
+            &token,
+            &DecodingKey::from_rsa(pubkey.clone()).unwrap(),
+            &Validation::new(alg),
+        )
+        .unwrap();
+        assert_eq!(my_claims, token_data.claims);
+    }
+    assert_eq!(kms.calls.load(std::sync::atomic::Ordering::SeqCst), RSA_ALGORITHMS.len());
+
+    let err = encode(&Header::new(Algorithm::HS256), &my_claims, &remote).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidAlgorithm), "{:?}", err);
+}
+
+/// Stands for an enclave, verifying with a key the caller never sees
+struct MockEnclave {
+    key: DecodingKey,
+}
+
+impl RemoteVerifier for MockEnclave {
+    fn verify(
+        &self,
+        message: &[u8],
+        signature: &[u8],
+        algorithm: Algorithm,
+    ) -> jsonwebtoken_rustcrypto::Result<bool> {
+        let message = std::str::from_utf8(message).unwrap();
+        verify(&URL_SAFE_NO_PAD.encode(signature), message, &self.key, algorithm)
+    }
+
+    fn family(&self) -> AlgorithmFamily {
+        AlgorithmFamily::Rsa
+    }
+}
+
+#[test]
+fn verify_with_remote_verifier() {
+    let (privkey, _) = rsa_keypair();
+    let remote = DecodingKey::from_remote_verifier(std::sync::Arc::new(MockEnclave {
+        key: DecodingKey::from_rsa(privkey.to_public_key()).unwrap(),
+    }));
+    assert_eq!(remote.family(), AlgorithmFamily::Rsa);
+    let my_claims = my_claims();
+    let token = encode(
+        &Header::new(Algorithm::RS256),
+        &my_claims,
+        &EncodingKey::from_rsa(privkey).unwrap(),
+    )
+    .unwrap();
+
+    let token_data = decode::<Claims>(&token, &remote, &Validation::new(Algorithm::RS256)).unwrap();
+    assert_eq!(my_claims, token_data.claims);
+
+    let parts: Vec<&str> = token.split('.').collect();
+    let tampered_claims = Claims { company: "EVIL".to_string(), ..my_claims };
+    let tampered_payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&tampered_claims).unwrap());
+    let tampered = [parts[0], &tampered_payload, parts[2]].join(".");
+    let err = decode::<Claims>(&tampered, &remote, &Validation::new(Algorithm::RS256)).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidSignature), "{:?}", err);
+}
+
+/// A remote RSA key that must not be asked about other algorithms
+struct RsaOnlyVerifier;
+
+impl RemoteVerifier for RsaOnlyVerifier {
+    fn verify(
+        &self,
+        _: &[u8],
+        _: &[u8],
+        algorithm: Algorithm,
+    ) -> jsonwebtoken_rustcrypto::Result<bool> {
+        panic!("called with {:?}", algorithm)
+    }
+
+    fn family(&self) -> AlgorithmFamily {
+        AlgorithmFamily::Rsa
+    }
+}
+
+#[test]
+fn remote_verifier_rejects_other_family() {
+    let remote = DecodingKey::from_remote_verifier(std::sync::Arc::new(RsaOnlyVerifier));
+    let token = encode(
+        &Header::new(Algorithm::HS256),
+        &serde_json::json!({ "sub": "b@b.com" }),
+        &EncodingKey::from_hmac_secret(b"secret"),
+    )
+    .unwrap();
+    let (message, signature) = token.rsplit_once('.').unwrap();
+
+    let err = verify(signature, message, &remote, Algorithm::HS256).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidAlgorithm), "{:?}", err);
+}
+
+#[test]
+fn pss_with_mgf1_hash() {
+    let (privkey, _) = rsa_keypair();
+    let decoding_key = DecodingKey::from_rsa(privkey.to_public_key()).unwrap();
+    let encoding_key = EncodingKey::from_rsa(privkey).unwrap();
+    let message = "eyJhbGciOiJQUzI1NiJ9.eyJzdWIiOiJiQGIuY29tIn0";
+
+    // The default MGF1 hash is the one used by the regular PS256 signatures
+    let signature = sign(message, &encoding_key, Algorithm::PS256).unwrap();
+    assert!(verify_pss_with_mgf1(
+        &signature,
+        message,
+        &decoding_key,
+        Algorithm::PS256,
+        Mgf1Hash::Sha256
+    )
+    .unwrap());
+    let signature =
+        sign_pss_with_mgf1(message, &encoding_key, Algorithm::PS256, Mgf1Hash::Sha256).unwrap();
+    assert!(verify(&signature, message, &decoding_key, Algorithm::PS256).unwrap());
+
+    // An explicit MGF1 hash only round trips with the same one
+    let signature =
+        sign_pss_with_mgf1(message, &encoding_key, Algorithm::PS256, Mgf1Hash::Sha512).unwrap();
+    assert!(verify_pss_with_mgf1(
+        &signature,
+        message,
+        &decoding_key,
+        Algorithm::PS256,
+        Mgf1Hash::Sha512
+    )
+    .unwrap());
+    assert!(!verify_pss_with_mgf1(
+        &signature,
+        message,
+        &decoding_key,
+        Algorithm::PS256,
+        Mgf1Hash::Sha256
+    )
+    .unwrap());
+    assert!(verify(&signature, message, &decoding_key, Algorithm::PS256).is_err());
+    let tampered = format!("{}x", message);
+    assert!(!verify_pss_with_mgf1(
+        &signature,
+        &tampered,
+        &decoding_key,
+        Algorithm::PS256,
+        Mgf1Hash::Sha512
+    )
+    .unwrap());
+
+    let err =
+        sign_pss_with_mgf1(message, &encoding_key, Algorithm::RS256, Mgf1Hash::Sha256).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidAlgorithm), "{:?}", err);
+}
+
+#[test]
+fn round_trip_pkcs8_der() {
+    use rsa::pkcs8::EncodePrivateKey;
+
+    let privkey: rsa::RsaPrivateKey =
+        rsa::pkcs8::DecodePrivateKey::from_pkcs8_pem(include_str!("private_rsa_key_pkcs8.pem"))
+            .unwrap();
+    let pubkey: rsa::RsaPublicKey =
+        rsa::pkcs8::DecodePublicKey::from_public_key_pem(include_str!("public_rsa_key_pkcs8.pem"))
+            .unwrap();
+    let pkcs8_der = privkey.to_pkcs8_der().unwrap();
+    let my_claims = my_claims();
+
+    let token = encode(
+        &Header::new(Algorithm::PS256),
+        &my_claims,
+        &EncodingKey::from_pkcs8_der(pkcs8_der.as_bytes()).unwrap(),
+    )
+    .unwrap();
+    let token_data = decode::<Claims>(
+        &token,
+        &DecodingKey::from_rsa(pubkey).unwrap(),
+        &Validation::new(Algorithm::PS256),
+    )
+    .unwrap();
+    assert_eq!(my_claims, token_data.claims);
+}
+
+#[test]
+fn pkcs8_der_unsupported_key_types() {
+    let err =
+        EncodingKey::from_pkcs8_der(include_bytes!("../ecdsa/private_ecdsa_key.pk8")).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::UnsupportedKeyType), "{:?}", err);
+
+    let err = EncodingKey::from_pkcs8_der(include_bytes!("private_rsa_key.der")).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidKeyFormat), "{:?}", err);
+}
+
+#[test]
+fn discouraged_algorithms_are_flagged() {
+    let my_claims = my_claims();
+    let (privkey, pubkey) = rsa_keypair();
+    let mut validation = Validation {
+        algorithms: vec![Algorithm::RS256, Algorithm::PS256],
+        ..Validation::default()
+    };
+    validation.set_discouraged_algorithms(&[Algorithm::RS256]);
+
+    // ES256 isn't supported by this crate, PS256 stands in as the preferred algorithm
+    for (alg, discouraged) in [(Algorithm::RS256, true), (Algorithm::PS256, false)] {
+        let token =
+            encode(&Header::new(alg), &my_claims, &EncodingKey::from_rsa(privkey.clone()).unwrap())
+                .unwrap();
+        let token_data =
+            decode::<Claims>(&token, &DecodingKey::from_rsa(pubkey.clone()).unwrap(), &validation)
+                .unwrap();
+        assert_eq!(my_claims, token_data.claims);
+        assert_eq!(token_data.used_discouraged_algorithm, discouraged);
+    }
+}
+
+#[test]
+fn header_new_with_kid() {
+    let header = Header::new_with_kid(Algorithm::RS256, "key-1");
+    assert_eq!(header.alg, Algorithm::RS256);
+    assert_eq!(header.kid, Some("key-1".to_string()));
+
+    let json = serde_json::to_string(&header).unwrap();
+    assert!(json.contains(r#""kid":"key-1""#), "{}", json);
+}
+
+#[test]
+fn decoding_key_algorithm_hint() {
+    let (_, pubkey) = rsa_keypair();
+    let key = DecodingKey::from_rsa(pubkey).unwrap();
+    // Both RS* and PS* work with RSA keys
+    assert_eq!(key.algorithm_hint(), None);
+    assert_eq!(key.family(), AlgorithmFamily::Rsa);
+
+    let key = DecodingKey::from_hmac_secret(b"secret");
+    assert_eq!(key.algorithm_hint(), None);
+    assert_eq!(key.family(), AlgorithmFamily::Hmac);
+}
+
+#[test]
+fn compute_signature_matches_sign() {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use jsonwebtoken_rustcrypto::crypto::{compute_signature, sign};
+
+    let (privkey, _) = rsa_keypair();
+    let key = EncodingKey::from_rsa(privkey).unwrap();
+
+    // PKCS#1 v1.5 signatures are deterministic, PSS ones are salted
+    for alg in [Algorithm::RS256, Algorithm::RS384, Algorithm::RS512] {
+        let bytes = compute_signature(b"hello world", &key, alg).unwrap();
+        assert_eq!(URL_SAFE_NO_PAD.encode(&bytes), sign("hello world", &key, alg).unwrap());
+    }
+}
+
+#[test]
+fn streaming_verifier_matches_verify() {
+    use jsonwebtoken_rustcrypto::crypto::{compute_signature, Verifier};
+
+    let (privkey, _) = rsa_keypair();
+    let pubkey = DecodingKey::from_rsa(privkey.to_public_key()).unwrap();
+    let encoding_key = EncodingKey::from_rsa(privkey).unwrap();
+    let message = "x".repeat(100_000);
+
+    for &alg in RSA_ALGORITHMS {
+        let signature = compute_signature(message.as_bytes(), &encoding_key, alg).unwrap();
+        let one_shot = verify(&URL_SAFE_NO_PAD.encode(&signature), &message, &pubkey, alg).unwrap();
+
+        let verifier = message
+            .as_bytes()
+            .chunks(4096)
+            .fold(Verifier::new(&pubkey, alg).unwrap(), |verifier, chunk| verifier.update(chunk));
+        assert_eq!(verifier.verify(&signature).unwrap(), one_shot);
+        assert!(one_shot);
+    }
+}
+
+#[test]
+fn streaming_signer_matches_sign() {
+    use jsonwebtoken_rustcrypto::crypto::Signer;
+
+    let (privkey, _) = rsa_keypair();
+    let pubkey = DecodingKey::from_rsa(privkey.to_public_key()).unwrap();
+    let key = EncodingKey::from_rsa(privkey).unwrap();
+    let message = "x".repeat(100_000);
+
+    for &alg in RSA_ALGORITHMS {
+        let signer = message
+            .as_bytes()
+            .chunks(4096)
+            .fold(Signer::new(&key, alg).unwrap(), |signer, chunk| signer.update(chunk));
+        let signature = URL_SAFE_NO_PAD.encode(signer.finish().unwrap());
+        assert!(verify(&signature, &message, &pubkey, alg).unwrap());
+
+        // PKCS#1 v1.5 signatures are deterministic, PSS ones are salted
+        if alg.to_string().starts_with("RS") {
+            assert_eq!(signature, sign(&message, &key, alg).unwrap());
+        }
+    }
+}
+
+#[test]
+fn pss_with_rsa_components() {
+    use rsa::traits::PublicKeyParts;
+
+    let (privkey, _) = rsa_keypair();
+    let n = URL_SAFE_NO_PAD.encode(privkey.n().to_bytes_be());
+    let e = URL_SAFE_NO_PAD.encode(privkey.e().to_bytes_be());
+    let key = DecodingKey::from_rsa_components(&n, &e).unwrap();
+    let encoding_key = EncodingKey::from_rsa(privkey).unwrap();
+    let my_claims = my_claims();
+
+    for alg in [Algorithm::PS256, Algorithm::PS384, Algorithm::PS512] {
+        let token = encode(&Header::new(alg), &my_claims, &encoding_key).unwrap();
+        let token_data = decode::<Claims>(&token, &key, &Validation::new(alg)).unwrap();
+        assert_eq!(my_claims, token_data.claims);
+    }
+}
+
+// There is no EC support, so there is no curve to get wrong: ES* tokens are rejected as an
+// algorithm mismatch before any signature check, whatever the key.
+#[test]
+fn ec_algorithms_rejected_before_verifying() {
+    let (privkey, _) = rsa_keypair();
+    let rsa_key = DecodingKey::from_rsa(privkey.to_public_key()).unwrap();
+    let hmac_key = DecodingKey::from_hmac_secret(b"secret");
+
+    for alg in [Algorithm::ES256, Algorithm::ES384] {
+        for key in [&rsa_key, &hmac_key] {
+            let err = verify("c2ln", "header.payload", key, alg).unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::InvalidAlgorithm), "{:?}", err);
+            let err = jsonwebtoken_rustcrypto::crypto::Verifier::new(key, alg).err().unwrap();
+            assert!(matches!(err.kind(), ErrorKind::InvalidAlgorithm), "{:?}", err);
+        }
+    }
+}
+
+#[test]
+fn rsa_key_size_policy() {
+    use jsonwebtoken_rustcrypto::crypto::RsaKeyPolicy;
+    use rand::SeedableRng;
+
+    // 2048 bits
+    let (privkey, _) = rsa_keypair();
+    let strict = RsaKeyPolicy::new().min_bits(3072);
+    let err = EncodingKey::from_rsa_with_policy(privkey.clone(), &strict).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidRsaKey), "{:?}", err);
+    let err = DecodingKey::from_rsa_with_policy(privkey.to_public_key(), &strict).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidRsaKey), "{:?}", err);
+    assert!(EncodingKey::from_rsa_with_policy(privkey.clone(), &RsaKeyPolicy::new()).is_ok());
+    let err = EncodingKey::from_rsa_with_policy(privkey, &RsaKeyPolicy::new().max_bits(1024))
+        .unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidRsaKey), "{:?}", err);
+
+    // Legacy keys are rejected by default but can be allowed
+    let mut rng = rand::rngs::StdRng::seed_from_u64(1024);
+    let legacy = rsa::RsaPrivateKey::new(&mut rng, 1024).unwrap();
+    assert!(EncodingKey::from_rsa_with_policy(legacy.clone(), &RsaKeyPolicy::new()).is_err());
+    assert!(EncodingKey::from_rsa_with_policy(legacy.clone(), &RsaKeyPolicy::new().min_bits(1024))
+        .is_ok());
+
+    // The last bound set wins, the other one is moved so the range isn't empty
+    assert_eq!(
+        RsaKeyPolicy::new().max_bits(1024),
+        RsaKeyPolicy::new().min_bits(1024).max_bits(1024)
+    );
+    assert_eq!(
+        RsaKeyPolicy::new().min_bits(20000),
+        RsaKeyPolicy::new().max_bits(20000).min_bits(20000)
+    );
+    assert!(EncodingKey::from_rsa_with_policy(legacy, &RsaKeyPolicy::new().max_bits(1024)).is_ok());
+}
@@ -0,0 +1,54 @@
+use jsonwebtoken_rustcrypto::errors::ErrorKind;
+use jsonwebtoken_rustcrypto::testkeys::{
+    generate_ec, generate_ed25519, generate_hmac, generate_rsa,
+};
+use jsonwebtoken_rustcrypto::{
+    decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+struct Claims {
+    sub: String,
+    exp: u64,
+}
+
+fn round_trip(algorithms: &[Algorithm], encoding_key: &EncodingKey, decoding_key: &DecodingKey) {
+    let claims = Claims { sub: "b@b.com".to_string(), exp: 10000000000 };
+    for &alg in algorithms {
+        let token = encode(&Header::new(alg), &claims, encoding_key).unwrap();
+        let token_data = decode::<Claims>(&token, decoding_key, &Validation::new(alg)).unwrap();
+        assert_eq!(claims, token_data.claims);
+    }
+}
+
+#[test]
+fn generated_rsa_keys_round_trip() {
+    let (encoding_key, decoding_key) = generate_rsa(2048).unwrap();
+    round_trip(Algorithm::rsa_all(), &encoding_key, &decoding_key);
+}
+
+#[test]
+fn generated_hmac_keys_round_trip() {
+    let (encoding_key, decoding_key) = generate_hmac(64);
+    round_trip(Algorithm::hmac_all(), &encoding_key, &decoding_key);
+
+    let (other_key, _) = generate_hmac(64);
+    let token = encode(
+        &Header::default(),
+        &Claims { sub: "b@b.com".to_string(), exp: 10000000000 },
+        &other_key,
+    )
+    .unwrap();
+    assert!(decode::<Claims>(&token, &decoding_key, &Validation::default()).is_err());
+}
+
+#[test]
+fn ec_and_ed25519_are_unsupported() {
+    for result in [generate_ec("P-256"), generate_ed25519()] {
+        let err = result.unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::UnsupportedKeyType), "{:?}", err);
+    }
+    let err = generate_ec("P-999").unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidKeyFormat), "{:?}", err);
+}